@@ -0,0 +1,288 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::Path;
+use usiem::events::field::SiemField;
+use usiem::events::field_dictionary;
+use usiem::events::webproxy::WebProxyRuleCategory;
+use usiem::events::{SiemEvent, SiemLog};
+use super::squidguard::categories::CategoryMapping;
+
+/// Categorizes domains and URLs with squidGuard `domains` and `urls` lists.
+///
+/// A domain entry matches the domain and all its subdomains, the deepest entry wins.
+/// An URL entry (`example.com/path`) matches the requests to that host, ignoring `www.`, whose path starts with it.
+/// URL entries are checked before the domains.
+#[derive(Clone, Debug, Default)]
+pub struct DomainCategorizer {
+    groups: Vec<(String, WebProxyRuleCategory)>,
+    root: DomainNode,
+}
+
+/// Suffix trie node: children are indexed by the next label from the right (`com` -> `example` -> `www`)
+#[derive(Clone, Debug, Default)]
+struct DomainNode {
+    children: BTreeMap<String, DomainNode>,
+    group: Option<usize>,
+    urls: Vec<(String, usize)>,
+}
+
+impl DomainCategorizer {
+    pub fn new() -> DomainCategorizer {
+        DomainCategorizer::default()
+    }
+
+    /// Adds the entries of a `domains` file to the group. The entries are compared like the requests,
+    /// without the `www.` or `ftp.` prefixes.
+    pub fn add_domains(&mut self, group: &str, category: WebProxyRuleCategory, content: &str) {
+        let group_id = self.group_id(group, category);
+        for domain in list_entries(content) {
+            let node = self.node_mut(strip_url_prefix(domain.trim_start_matches('.')));
+            node.group = Some(group_id);
+        }
+    }
+
+    /// Adds the entries of an `urls` file to the group
+    pub fn add_urls(&mut self, group: &str, category: WebProxyRuleCategory, content: &str) {
+        let group_id = self.group_id(group, category);
+        for url in list_entries(content) {
+            let url = strip_url_prefix(&url);
+            let (host, path) = match url.find('/') {
+                Some(pos) => (&url[..pos], &url[pos..]),
+                None => (url, "/"),
+            };
+            let node = self.node_mut(host);
+            node.urls.push((path.to_string(), group_id));
+        }
+    }
+
+    /// Loads a squidGuard database: each directory with `domains` or `urls` files is a group named after
+    /// its path (`finance/banking`) and categorized with the mapping.
+    pub fn load_db<P: AsRef<Path>>(&mut self, dbhome: P, categories: &CategoryMapping) -> Result<(), String> {
+        let dbhome = dbhome.as_ref();
+        self.load_db_dir(dbhome, "", categories)
+    }
+
+    fn load_db_dir(&mut self, dir: &Path, group: &str, categories: &CategoryMapping) -> Result<(), String> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) => return Err(format!("Cannot read {}: {}", dir.display(), err)),
+        };
+        let mut paths: Vec<_> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+        paths.sort();
+        for path in paths {
+            let name = match path.file_name().and_then(|v| v.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            if path.is_dir() {
+                let child = if group.is_empty() { name } else { format!("{}/{}", group, name) };
+                self.load_db_dir(&path, &child, categories)?;
+            } else if !group.is_empty() && (name == "domains" || name == "urls") {
+                let content = match std::fs::read_to_string(&path) {
+                    Ok(content) => content,
+                    Err(err) => return Err(format!("Cannot read {}: {}", path.display(), err)),
+                };
+                if name == "domains" {
+                    self.add_domains(group, categories.category(group), &content);
+                } else {
+                    self.add_urls(group, categories.category(group), &content);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Group and category of the request
+    pub fn categorize(&self, domain: &str, url: &str) -> Option<(&str, &WebProxyRuleCategory)> {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        let host = strip_url_prefix(&domain);
+        let path = url_path(url);
+        let mut node = &self.root;
+        let mut found = None;
+        let mut labels = host.rsplit('.').peekable();
+        while let Some(label) = labels.next() {
+            node = match node.children.get(label) {
+                Some(child) => child,
+                None => break,
+            };
+            if node.group.is_some() {
+                found = node.group;
+            }
+            if labels.peek().is_none() {
+                let url_group = node
+                    .urls
+                    .iter()
+                    .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+                    .max_by_key(|(prefix, _)| prefix.len())
+                    .map(|(_, group)| *group);
+                if url_group.is_some() {
+                    found = url_group;
+                }
+            }
+        }
+        found.map(|id| {
+            let (name, category) = &self.groups[id];
+            (name.as_str(), category)
+        })
+    }
+
+    /// Sets the rule name and category of WebProxy events that do not have one yet
+    pub fn categorize_log(&self, log: &mut SiemLog) {
+        let mut event = match log.event() {
+            SiemEvent::WebProxy(event) => event.clone(),
+            _ => return,
+        };
+        match &event.rule_category {
+            Some(WebProxyRuleCategory::Uncategorized) | None => {}
+            Some(_) => return,
+        }
+        let (group, category) = match self.categorize(&event.domain, &event.url) {
+            Some((group, category)) => (group.to_string(), category.clone()),
+            None => return,
+        };
+        event.rule_name = Some(Cow::Owned(group));
+        event.rule_category = Some(category);
+        // Setting the event writes again the standard fields. The parsers store the byte counters as U64.
+        let destination_bytes = log.field(field_dictionary::DESTINATION_BYTES).cloned();
+        let source_bytes = log.field(field_dictionary::SOURCE_BYTES).cloned();
+        log.set_event(SiemEvent::WebProxy(event));
+        restore_field(log, field_dictionary::DESTINATION_BYTES, destination_bytes);
+        restore_field(log, field_dictionary::SOURCE_BYTES, source_bytes);
+    }
+
+    fn group_id(&mut self, group: &str, category: WebProxyRuleCategory) -> usize {
+        match self.groups.iter().position(|(name, _)| name == group) {
+            Some(pos) => {
+                self.groups[pos].1 = category;
+                pos
+            }
+            None => {
+                self.groups.push((group.to_string(), category));
+                self.groups.len() - 1
+            }
+        }
+    }
+
+    fn node_mut(&mut self, domain: &str) -> &mut DomainNode {
+        let mut node = &mut self.root;
+        for label in domain.rsplit('.') {
+            node = node.children.entry(label.to_string()).or_insert_with(DomainNode::default);
+        }
+        node
+    }
+}
+
+fn restore_field(log: &mut SiemLog, name: &str, value: Option<SiemField>) {
+    if let Some(value) = value {
+        log.add_field(name, value);
+    }
+}
+
+/// Non empty lines in lowercase, without comments
+fn list_entries(content: &str) -> impl Iterator<Item = String> + '_ {
+    content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_lowercase())
+}
+
+/// squidGuard compares the URLs without the protocol and the `www.` or `ftp.` prefixes
+fn strip_url_prefix(url: &str) -> &str {
+    let url = match url.find("://") {
+        Some(pos) => &url[pos + 3..],
+        None => url,
+    };
+    if (url.starts_with("www.") || url.starts_with("ftp.")) && url[4..].contains('.') {
+        &url[4..]
+    } else {
+        url
+    }
+}
+
+/// Path of the URL as logged by Squid: absolute, origin-form or authority-form
+fn url_path(url: &str) -> &str {
+    let url = match url.find("://") {
+        Some(pos) => &url[pos + 3..],
+        None => url,
+    };
+    if url.starts_with('/') {
+        return url;
+    }
+    match url.find('/') {
+        Some(pos) => &url[pos..],
+        None => "/",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DomainCategorizer;
+    use crate::squid::SquidParser;
+    use usiem::components::common::LogParser;
+    use usiem::events::field::{SiemField, SiemIp};
+    use usiem::events::field_dictionary;
+    use usiem::events::webproxy::WebProxyRuleCategory;
+    use usiem::events::SiemLog;
+
+    fn categorizer() -> DomainCategorizer {
+        let mut categorizer = DomainCategorizer::new();
+        categorizer.add_domains("porn", WebProxyRuleCategory::Pornography, "pornpage.com\n# comment\n\n.adult.example.org");
+        categorizer.add_domains("search", WebProxyRuleCategory::SearchEngines, "google.com");
+        categorizer.add_domains("maps", WebProxyRuleCategory::Informational, "maps.google.com");
+        categorizer.add_domains("news", WebProxyRuleCategory::News, "www.example.com\nFTP.Example.net");
+        categorizer.add_urls("hacking", WebProxyRuleCategory::Hacking, "www.example.org/tools/\nhttp://google.com/hack");
+        categorizer
+    }
+
+    #[test]
+    fn test_categorize() {
+        let categorizer = categorizer();
+        assert_eq!(categorizer.categorize("pornpage.com", "/"), Some(("porn", &WebProxyRuleCategory::Pornography)));
+        assert_eq!(categorizer.categorize("WWW.PornPage.com", "/"), Some(("porn", &WebProxyRuleCategory::Pornography)));
+        assert_eq!(categorizer.categorize("notpornpage.com", "/"), None);
+        assert_eq!(categorizer.categorize("www.google.com", "/search?q=test"), Some(("search", &WebProxyRuleCategory::SearchEngines)));
+        assert_eq!(categorizer.categorize("a.maps.google.com", "/"), Some(("maps", &WebProxyRuleCategory::Informational)));
+        assert_eq!(categorizer.categorize("www.google.com", "http://www.google.com/hack/index.html"), Some(("hacking", &WebProxyRuleCategory::Hacking)));
+        assert_eq!(categorizer.categorize("example.org", "/tools/nmap"), Some(("hacking", &WebProxyRuleCategory::Hacking)));
+        assert_eq!(categorizer.categorize("example.org", "/"), None);
+        assert_eq!(categorizer.categorize("sub.adult.example.org", "/"), Some(("porn", &WebProxyRuleCategory::Pornography)));
+        // Entries with the www. or ftp. prefix
+        assert_eq!(categorizer.categorize("www.example.com", "/"), Some(("news", &WebProxyRuleCategory::News)));
+        assert_eq!(categorizer.categorize("example.com", "/"), Some(("news", &WebProxyRuleCategory::News)));
+        assert_eq!(categorizer.categorize("ftp.example.net", "/"), Some(("news", &WebProxyRuleCategory::News)));
+    }
+
+    #[test]
+    fn test_categorize_log() {
+        let categorizer = categorizer();
+        let log = "1613260836.628    287 172.17.0.1 TCP_TUNNEL_ABORTED/200 18353 CONNECT www.google.com:443 - HIER_DIRECT/142.250.184.4 -";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match SquidParser::new().parse_log(log) {
+            Ok(mut log) => {
+                categorizer.categorize_log(&mut log);
+                assert_eq!(log.field(field_dictionary::RULE_NAME), Some(&SiemField::from_str("search")));
+                assert_eq!(log.field(field_dictionary::RULE_CATEGORY), Some(&SiemField::from_str("SearchEngines")));
+                assert_eq!(log.field(field_dictionary::DESTINATION_BYTES), Some(&SiemField::U64(18353)));
+            }
+            Err(_) => panic!("Cannot parse log"),
+        }
+    }
+
+    #[test]
+    fn test_load_db() {
+        let dbhome = std::env::temp_dir().join(format!("usiem-squid-db-{}", std::process::id()));
+        std::fs::create_dir_all(dbhome.join("finance/trading")).expect("Must work");
+        std::fs::create_dir_all(dbhome.join("porn")).expect("Must work");
+        std::fs::write(dbhome.join("finance/trading/domains"), "broker.com\n").expect("Must work");
+        std::fs::write(dbhome.join("porn/domains"), "pornpage.com\n").expect("Must work");
+        std::fs::write(dbhome.join("porn/urls"), "example.com/adult\n").expect("Must work");
+        let mut categorizer = DomainCategorizer::new();
+        let loaded = categorizer.load_db(&dbhome, &crate::squidguard::categories::CategoryMapping::new());
+        std::fs::remove_dir_all(&dbhome).expect("Must work");
+        assert_eq!(loaded, Ok(()));
+        assert_eq!(categorizer.categorize("www.broker.com", "/"), Some(("finance/trading", &WebProxyRuleCategory::Trading)));
+        assert_eq!(categorizer.categorize("pornpage.com", "/"), Some(("porn", &WebProxyRuleCategory::Pornography)));
+        assert_eq!(categorizer.categorize("example.com", "http://example.com/adult/index.html"), Some(("porn", &WebProxyRuleCategory::Pornography)));
+    }
+}
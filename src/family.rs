@@ -0,0 +1,192 @@
+use std::sync::Arc;
+use usiem::components::common::{LogParser, LogParsingError};
+use usiem::events::SiemLog;
+use super::squid;
+use super::squidguard;
+use super::categorizer::DomainCategorizer;
+use super::squid::cache_log::{self, SquidCacheLogParser};
+use super::squid::SquidParser;
+use super::squidguard::categories::CategoryMapping;
+use super::squidguard::SquidGuardParser;
+use super::syslog::split_syslog;
+use super::timezone::LogTimezone;
+
+/// Type of log detected by sniffing the line header
+#[derive(Debug, PartialEq, Clone)]
+pub enum SquidLogType {
+    /// Squid access.log: `1613260836.628    287 172.17.0.1 ...`
+    Squid,
+    /// SquidGuard log: `2021-02-14 00:02:33 [26] Request(...` or service lines like `2021-02-14 00:02:33 [26] squidGuard stopped`
+    SquidGuard,
+    /// Squid cache.log: `2021/02/14 00:02:33 kid1| Starting Squid Cache version 4.13...`
+    CacheLog,
+}
+
+/// Parser for mixed Squid access, Squid cache.log and SquidGuard logs, with or without syslog header.
+/// Each line is sent to the parser of the module that generated it.
+#[derive(Clone)]
+pub struct SquidFamilyParser {
+    squid: SquidParser,
+    squidguard: SquidGuardParser,
+    cache_log: SquidCacheLogParser,
+}
+
+impl Default for SquidFamilyParser {
+    fn default() -> SquidFamilyParser {
+        SquidFamilyParser::new()
+    }
+}
+
+impl SquidFamilyParser {
+    pub fn new() -> SquidFamilyParser {
+        SquidFamilyParser {
+            squid: SquidParser::new(),
+            squidguard: SquidGuardParser::new(),
+            cache_log: SquidCacheLogParser::new(),
+        }
+    }
+    /// Timezone of the SquidGuard logs and the Squid cache.log. Squid access logs use epoch timestamps and are not affected.
+    pub fn with_timezone(mut self, timezone: LogTimezone) -> SquidFamilyParser {
+        self.cache_log = self.cache_log.with_timezone(timezone.clone());
+        self.squidguard = self.squidguard.with_timezone(timezone);
+        self
+    }
+    /// Categories of the SquidGuard destination groups
+    pub fn with_categories(mut self, categories: CategoryMapping) -> SquidFamilyParser {
+        self.squidguard = self.squidguard.with_categories(categories);
+        self
+    }
+    /// Categorizes the Squid requests using squidGuard domain lists
+    pub fn with_categorizer(mut self, categorizer: Arc<DomainCategorizer>) -> SquidFamilyParser {
+        self.squid = self.squid.with_categorizer(categorizer);
+        self
+    }
+}
+
+impl LogParser for SquidFamilyParser {
+    fn parse_log(&self, log: SiemLog) -> Result<SiemLog, LogParsingError> {
+        match detect_log_type(log.message()) {
+            Some(SquidLogType::Squid) => self.squid.parse_log(log),
+            Some(SquidLogType::SquidGuard) => self.squidguard.parse_log(log),
+            Some(SquidLogType::CacheLog) => self.cache_log.parse_log(log),
+            None => Err(LogParsingError::NoValidParser(log)),
+        }
+    }
+    fn device_match(&self, log: &SiemLog) -> bool {
+        detect_log_type(log.message()).is_some()
+    }
+    fn name(&self) -> &str {
+        "SquidFamily"
+    }
+    fn description(&self) -> &str {
+        "Squid access, Squid cache.log and SquidGuard log parser with automatic detection of the log type"
+    }
+}
+
+pub fn parse_log(log: SiemLog) -> Result<SiemLog, LogParsingError> {
+    parse_log_with_timezone(log, &LogTimezone::Utc)
+}
+
+pub fn parse_log_with_timezone(log: SiemLog, timezone: &LogTimezone) -> Result<SiemLog, LogParsingError> {
+    match detect_log_type(log.message()) {
+        Some(SquidLogType::Squid) => squid::parse_log(log),
+        Some(SquidLogType::SquidGuard) => squidguard::parse_log_with_timezone(log, timezone),
+        Some(SquidLogType::CacheLog) => cache_log::parse_log_with_timezone(log, timezone),
+        None => Err(LogParsingError::NoValidParser(log)),
+    }
+}
+
+/// Sniffs the header of the line to know which module generated it
+pub fn detect_log_type(log_line: &str) -> Option<SquidLogType> {
+    if squidguard::split_header(log_line).is_some() || split_syslog(log_line, "squidGuard").is_some() {
+        return Some(SquidLogType::SquidGuard);
+    }
+    if cache_log::split_header(log_line).is_some() || cache_log::split_syslog_message(log_line).is_some() {
+        return Some(SquidLogType::CacheLog);
+    }
+    let log_content = if log_line.starts_with("<") {
+        match split_syslog(log_line, "squid") {
+            Some((_, message)) => message,
+            None => return None,
+        }
+    } else {
+        log_line
+    };
+    let first_column = match log_content.find(" ") {
+        Some(pos) => &log_content[..pos],
+        None => log_content,
+    };
+    if is_epoch(first_column) {
+        Some(SquidLogType::Squid)
+    } else {
+        None
+    }
+}
+
+fn is_epoch(text: &str) -> bool {
+    match text.find(".") {
+        Some(pos) => {
+            pos > 0
+                && text[..pos].chars().all(|c| c.is_ascii_digit())
+                && text[pos + 1..].chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{detect_log_type, SquidFamilyParser, SquidLogType};
+    use usiem::components::common::{LogParser, LogParsingError};
+    use usiem::events::field::{SiemField, SiemIp};
+    use usiem::events::field_dictionary;
+    use usiem::events::SiemLog;
+
+    #[test]
+    fn test_detect_log_type() {
+        assert_eq!(detect_log_type("1613260836.628    287 172.17.0.1 TCP_TUNNEL_ABORTED/200 18353 CONNECT www.google.com:443 - HIER_DIRECT/142.250.184.4 -"), Some(SquidLogType::Squid));
+        assert_eq!(detect_log_type("<1>1 2020-09-25T16:23:25+02:00 OPNsense.localdomain (squid-1)[91300]: 1601051005.952  18459 192.168.4.100 TCP_TUNNEL/200 7323 CONNECT ap.lijit.com:443 - HIER_DIRECT/72.251.249.9 -"), Some(SquidLogType::Squid));
+        assert_eq!(detect_log_type("2021-02-14 00:02:33 [26] Request(default/porn/-) pornpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT"), Some(SquidLogType::SquidGuard));
+        assert_eq!(detect_log_type("<13>Feb 14 00:02:33 proxy squidGuard: 2021-02-14 00:02:33 [26] Request(default/porn/-) pornpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT"), Some(SquidLogType::SquidGuard));
+        assert_eq!(detect_log_type("2021-02-14 00:02:33 [26] Going into emergency mode"), Some(SquidLogType::SquidGuard));
+        assert_eq!(detect_log_type("2021/02/14 00:02:33 kid1| WARNING: The redirector helpers are crashing too rapidly, need help!"), Some(SquidLogType::CacheLog));
+        assert_eq!(detect_log_type("<13>Feb 14 00:02:33 proxy squidGuard[26]: Request(default/porn/-) pornpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT"), Some(SquidLogType::SquidGuard));
+        assert_eq!(detect_log_type("<11>Feb 14 00:02:33 proxy squid[26]: kid1| ERROR: DNS failure while resolving example.local: Name error."), Some(SquidLogType::CacheLog));
+        assert_eq!(detect_log_type("<11>Feb 14 00:02:33 proxy squid[26]: FATAL: Bungled /etc/squid/squid.conf line 12: acl localnet src"), Some(SquidLogType::CacheLog));
+        assert_eq!(detect_log_type("<13>Feb 14 00:02:33 proxy squid[26]: 2001:db8::1 - - [14/Feb/2021:00:02:33 +0000] \"GET http://www.example.com/ HTTP/1.1\" 200 1024 TCP_MISS:HIER_DIRECT"), None);
+        assert_eq!(detect_log_type("<13>Feb 14 00:02:33 host sshd[22]: Accepted password for root"), None);
+        assert_eq!(detect_log_type("GET /index.html HTTP/1.1"), None);
+    }
+
+    #[test]
+    fn test_parse_mixed_logs() {
+        let parser = SquidFamilyParser::new();
+        let squid_log = SiemLog::new("1613260836.628    287 172.17.0.1 TCP_TUNNEL_ABORTED/200 18353 CONNECT www.google.com:443 - HIER_DIRECT/142.250.184.4 -".to_string(), 0, SiemIp::V4(0));
+        let guard_log = SiemLog::new("2021-02-14 00:02:33 [26] Request(default/porn/-) pornpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT".to_string(), 0, SiemIp::V4(0));
+        match parser.parse_log(squid_log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::URL_DOMAIN), Some(&SiemField::from_str("www.google.com")));
+            }
+            Err(_) => panic!("Cannot parse log"),
+        }
+        match parser.parse_log(guard_log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::RULE_CATEGORY), Some(&SiemField::from_str("Pornography")));
+            }
+            Err(_) => panic!("Cannot parse log"),
+        }
+        let cache_log = SiemLog::new("2021/02/14 00:02:33 kid1| Starting Squid Cache version 4.13 for x86_64-pc-linux-gnu...".to_string(), 0, SiemIp::V4(0));
+        match parser.parse_log(cache_log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::EVENT_ACTION), Some(&SiemField::from_str("start")));
+            }
+            Err(_) => panic!("Cannot parse log"),
+        }
+        let other_log = SiemLog::new("<13>Feb 14 00:02:33 host sshd[22]: Accepted password for root".to_string(), 0, SiemIp::V4(0));
+        assert!(!parser.device_match(&other_log));
+        match parser.parse_log(other_log) {
+            Err(LogParsingError::NoValidParser(_)) => {}
+            _ => panic!("Must not be parsed"),
+        }
+    }
+}
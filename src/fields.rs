@@ -0,0 +1,67 @@
+// Fields generated by the Squid parsers that are not included in the uSIEM field dictionary.
+// Names follow the Elastic Common Schema when possible: https://www.elastic.co/guide/en/ecs/current/index.html
+
+/// Hostname of the client when Squid logs FQDNs instead of IPs
+pub static SOURCE_DOMAIN: &str = "source.domain";
+/// Fragment of the URL without the `#` character
+pub static URL_FRAGMENT: &str = "url.fragment";
+/// Decoded query parameters as a JSON object: `{"q":"squid proxy","tag":["a","b&c"]}`
+pub static URL_QUERY_PARAMS: &str = "url.query_params";
+/// Unmodified original user agent string
+pub static USER_AGENT_ORIGINAL: &str = "user_agent.original";
+/// Referrer of the HTTP request
+pub static HTTP_REQUEST_REFERRER: &str = "http.request.referrer";
+/// Prefix for request headers logged with `%{Header}>h`. The header name is added in lowercase.
+pub static HTTP_REQUEST_HEADERS: &str = "http.request.headers";
+/// Prefix for reply headers logged with `%{Header}<h`. The header name is added in lowercase.
+pub static HTTP_RESPONSE_HEADERS: &str = "http.response.headers";
+
+/// Squid result code as written in the log: TCP_MISS, TCP_DENIED...
+pub static SQUID_RESULT_CODE: &str = "squid.result.code";
+/// Protocol tag of the Squid result code: TCP, UDP or NONE
+pub static SQUID_RESULT_PROTOCOL: &str = "squid.result.protocol";
+/// Cache result of the Squid result code: HIT, MISS, REFRESH_UNMODIFIED...
+pub static SQUID_RESULT_CACHE: &str = "squid.result.cache";
+/// Suffixes of the Squid result code separated by commas: ABORTED, TIMEDOUT, IGNORED
+pub static SQUID_RESULT_SUFFIX: &str = "squid.result.suffix";
+/// Squid hierarchy code as written in the log: HIER_DIRECT, FIRSTUP_PARENT...
+pub static SQUID_HIERARCHY_CODE: &str = "squid.hierarchy.code";
+/// Name of the cache peer that handled the request when it is logged as a hostname
+pub static SQUID_PEER_HOST: &str = "squid.peer.host";
+/// Hostname of the origin server when Squid logs it instead of the IP and the request did not go through a cache peer
+pub static DESTINATION_DOMAIN: &str = "destination.domain";
+
+/// PID of the process that wrote the log
+pub static PROCESS_PID: &str = "process.pid";
+/// Version of the service, as written in the startup logs
+pub static SERVICE_VERSION: &str = "service.version";
+/// Full path of the file the log refers to
+pub static FILE_PATH: &str = "file.path";
+/// Error message written by the service
+pub static ERROR_MESSAGE: &str = "error.message";
+/// Type of squidGuard list loaded: domains, urls or expressions
+pub static SQUIDGUARD_DB_LIST: &str = "squidguard.db.list";
+/// Number of domains loaded from a squidGuard list
+pub static SQUIDGUARD_DB_DOMAINS: &str = "squidguard.db.domains";
+/// Number of URLs loaded from a squidGuard list
+pub static SQUIDGUARD_DB_URLS: &str = "squidguard.db.urls";
+/// Action column of the squidGuard request logs: REDIRECT, PASS, REWRITE...
+pub static SQUIDGUARD_ACTION: &str = "squidguard.action";
+/// New URL of a request rewritten by squidGuard
+pub static SQUIDGUARD_REWRITE_TARGET: &str = "squidguard.rewrite.target";
+/// squidGuard source group (acl) that matched the client: `students` in `Request(students/porn/-)`
+pub static SQUIDGUARD_SOURCE_GROUP: &str = "squidguard.source.group";
+/// squidGuard destination group that matched the request: `porn` in `Request(students/porn/-)`
+pub static SQUIDGUARD_DESTINATION_GROUP: &str = "squidguard.destination.group";
+/// Extra tag of the squidGuard request log, the rewrite rule applied: `safesearch` in `Request(students/none/safesearch)`
+pub static SQUIDGUARD_TAG: &str = "squidguard.tag";
+/// squid.conf `http_access` rule that decided the request: `deny CONNECT !SSL_ports`
+pub static SQUID_HTTP_ACCESS_RULE: &str = "squid.http_access.rule";
+/// Line of the `http_access` rule in squid.conf
+pub static SQUID_HTTP_ACCESS_LINE: &str = "squid.http_access.line";
+/// ACL names of the `http_access` rule separated by commas, negated ones with `!`
+pub static SQUID_HTTP_ACCESS_ACLS: &str = "squid.http_access.acls";
+/// Severity of the service logs: fatal, error, warning or info
+pub static LOG_LEVEL: &str = "log.level";
+/// Squid SMP process (kid) that wrote the cache.log line: 1 in `kid1|`
+pub static SQUID_KID: &str = "squid.kid";
@@ -1,2 +1,8 @@
+pub mod categorizer;
+pub mod family;
+pub mod fields;
+pub mod matching;
 pub mod squid;
-pub mod squidguard;
\ No newline at end of file
+pub mod squidguard;
+pub mod syslog;
+pub mod timezone;
\ No newline at end of file
@@ -0,0 +1,86 @@
+use std::net::IpAddr;
+use chrono::NaiveTime;
+
+/// Matches an address, a network (`10.0.0.0/8`, `10.0.0.0/255.0.0.0`) or a range (`10.0.0.1-10.0.0.20`)
+pub fn ip_matches(entry: &str, ip: &IpAddr) -> bool {
+    let value = ip_value(ip);
+    if let Some(pos) = entry.find('-') {
+        return match (entry[..pos].parse::<IpAddr>(), entry[pos + 1..].parse::<IpAddr>()) {
+            (Ok(start), Ok(end)) => same_family(&start, ip) && ip_value(&start) <= value && value <= ip_value(&end),
+            _ => false,
+        };
+    }
+    let (network, mask) = match entry.find('/') {
+        Some(pos) => (&entry[..pos], Some(&entry[pos + 1..])),
+        None => (entry, None),
+    };
+    let network = match network.parse::<IpAddr>() {
+        Ok(network) if same_family(&network, ip) => network,
+        _ => return false,
+    };
+    let bits = if network.is_ipv4() { 32 } else { 128 };
+    let prefix = match mask {
+        None => bits,
+        Some(mask) => match (mask.parse::<u32>(), mask.parse::<IpAddr>()) {
+            (Ok(prefix), _) if prefix <= bits => prefix,
+            (_, Ok(mask)) if same_family(&mask, ip) => ip_value(&mask).count_ones(),
+            _ => return false,
+        },
+    };
+    if prefix == 0 {
+        return true;
+    }
+    let shift = bits - prefix;
+    (ip_value(&network) >> shift) == (value >> shift)
+}
+
+fn ip_value(ip: &IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(ip) => u32::from(*ip) as u128,
+        IpAddr::V6(ip) => u128::from(*ip),
+    }
+}
+
+fn same_family(a: &IpAddr, b: &IpAddr) -> bool {
+    a.is_ipv4() == b.is_ipv4()
+}
+
+/// `08:00-17:00`, the end is not included. `24:00` is accepted as the end of the day.
+pub fn time_range_matches(range: &str, time: &NaiveTime) -> bool {
+    let pos = match range.find('-') {
+        Some(pos) => pos,
+        None => return false,
+    };
+    match (NaiveTime::parse_from_str(&range[..pos], "%H:%M"), NaiveTime::parse_from_str(&range[pos + 1..], "%H:%M")) {
+        (Ok(start), Ok(end)) => start <= *time && *time < end,
+        (Ok(start), Err(_)) if &range[pos + 1..] == "24:00" => start <= *time,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ip_matches, time_range_matches};
+    use chrono::NaiveTime;
+
+    #[test]
+    fn test_ip_matches() {
+        let ip = "10.2.0.15".parse().expect("Must work");
+        assert!(ip_matches("10.2.0.15", &ip));
+        assert!(ip_matches("10.0.0.0/8", &ip));
+        assert!(ip_matches("10.2.0.0/255.255.255.0", &ip));
+        assert!(ip_matches("10.2.0.1-10.2.0.20", &ip));
+        assert!(!ip_matches("10.3.0.0/16", &ip));
+        assert!(!ip_matches("fd00::/8", &ip));
+        assert!(ip_matches("fd00::/8", &"fd12::1".parse().expect("Must work")));
+    }
+
+    #[test]
+    fn test_time_range_matches() {
+        let time = NaiveTime::from_hms_opt(17, 0, 0).expect("Must work");
+        assert!(time_range_matches("08:00-17:01", &time));
+        assert!(!time_range_matches("08:00-17:00", &time));
+        assert!(time_range_matches("17:00-24:00", &time));
+        assert!(!time_range_matches("17:00", &time));
+    }
+}
@@ -0,0 +1,386 @@
+use std::borrow::Cow;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use chrono::{Datelike, NaiveDateTime, Weekday};
+use usiem::events::common::{HttpMethod, WebProtocol};
+use usiem::events::field::{SiemField, SiemIp};
+use usiem::events::webproxy::WebProxyEvent;
+use usiem::events::{SiemEvent, SiemLog};
+use super::config::{Acl, AclType, HttpAccessRule, SquidConfig};
+use crate::fields;
+use crate::matching::{ip_matches, time_range_matches};
+use crate::timezone::LogTimezone;
+
+/// Request data checked by the `http_access` ACLs
+#[derive(Debug, Clone)]
+pub struct AccessRequest<'a> {
+    pub client_ip: IpAddr,
+    /// Server address, if known
+    pub server_ip: Option<IpAddr>,
+    pub domain: &'a str,
+    pub port: u16,
+    pub method: &'a str,
+    /// URL scheme in lowercase, empty for CONNECT requests
+    pub protocol: &'a str,
+    /// Authenticated user, empty if none
+    pub user: &'a str,
+    /// Local time of the request
+    pub time: NaiveDateTime,
+}
+
+/// Result of the `http_access` rule chain
+#[derive(Debug, PartialEq, Clone)]
+pub struct AccessDecision<'a> {
+    pub allow: bool,
+    /// Rule that matched. None when no rule matched and the opposite of the last rule was applied.
+    pub rule: Option<&'a HttpAccessRule>,
+}
+
+impl<'a> AccessDecision<'a> {
+    /// Text of the rule: `deny CONNECT !SSL_ports`, or `implicit deny`
+    pub fn description(&self) -> String {
+        match self.rule {
+            Some(rule) => rule.to_string(),
+            None => format!("implicit {}", if self.allow { "allow" } else { "deny" }),
+        }
+    }
+}
+
+/// Replays access log requests through the `http_access` rules of a squid.conf to explain why they were denied.
+///
+/// The regular expression ACLs and the ACL types not listed in `AclType` cannot be evaluated.
+/// If the decision depends on one of them there is no explanation.
+#[derive(Clone)]
+pub struct HttpAccessEvaluator {
+    config: SquidConfig,
+    timezone: LogTimezone,
+}
+
+impl HttpAccessEvaluator {
+    pub fn new(config: SquidConfig) -> HttpAccessEvaluator {
+        HttpAccessEvaluator {
+            config,
+            timezone: LogTimezone::Utc,
+        }
+    }
+    /// Timezone of the proxy, used by the `time` ACLs. Defaults to UTC.
+    pub fn with_timezone(mut self, timezone: LogTimezone) -> HttpAccessEvaluator {
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn config(&self) -> &SquidConfig {
+        &self.config
+    }
+
+    /// Checks the rules in order. None if the decision depends on an ACL that cannot be evaluated.
+    pub fn evaluate(&self, request: &AccessRequest) -> Option<AccessDecision<'_>> {
+        for rule in &self.config.http_access {
+            if self.rule_matches(rule, request)? {
+                return Some(AccessDecision { allow: rule.allow, rule: Some(rule) });
+            }
+        }
+        let allow = match self.config.http_access.last() {
+            Some(rule) => !rule.allow,
+            // Without rules Squid denies everything
+            None => false,
+        };
+        Some(AccessDecision { allow, rule: None })
+    }
+
+    /// Evaluates a WebProxy event parsed from the access log. `timestamp` is the time of the request in milliseconds.
+    pub fn evaluate_event(&self, event: &WebProxyEvent, timestamp: i64) -> Option<AccessDecision<'_>> {
+        let method = match &event.http_method {
+            HttpMethod::UNKNOWN(method) => method.to_uppercase(),
+            method => method.to_string(),
+        };
+        let protocol = match (&event.http_method, &event.protocol) {
+            (HttpMethod::CONNECT, _) => String::new(),
+            (_, WebProtocol::UNKNOWN(protocol)) => protocol.to_lowercase(),
+            (_, protocol) => protocol.to_string().to_lowercase(),
+        };
+        let server_ip = match &event.destination_ip {
+            SiemIp::V4(0) => None,
+            ip => Some(ip_addr(ip)),
+        };
+        let request = AccessRequest {
+            client_ip: ip_addr(&event.source_ip),
+            server_ip,
+            domain: &event.domain,
+            port: event.destination_port,
+            method: &method,
+            protocol: &protocol,
+            user: &event.user_name,
+            time: self.timezone.local_datetime(timestamp)?,
+        };
+        self.evaluate(&request)
+    }
+
+    /// Adds the `http_access` rule and its ACL names to a `TCP_DENIED` request.
+    /// Returns the rule, None if the log is not a denied request or the rules do not explain it.
+    pub fn explain_log(&self, log: &mut SiemLog) -> Option<String> {
+        match log.field(fields::SQUID_RESULT_CODE) {
+            Some(SiemField::Text(code)) if code.contains("DENIED") => {}
+            _ => return None,
+        }
+        let (description, line, acls) = {
+            let event = match log.event() {
+                SiemEvent::WebProxy(event) => event,
+                _ => return None,
+            };
+            let decision = self.evaluate_event(event, log.event_created())?;
+            if decision.allow {
+                return None;
+            }
+            let (line, acls) = match decision.rule {
+                Some(rule) => (Some(rule.line), rule.acls.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(",")),
+                None => (None, String::new()),
+            };
+            (decision.description(), line, acls)
+        };
+        log.add_field(fields::SQUID_HTTP_ACCESS_RULE, SiemField::Text(Cow::Owned(description.to_string())));
+        if let Some(line) = line {
+            log.add_field(fields::SQUID_HTTP_ACCESS_LINE, SiemField::U64(line as u64));
+        }
+        if !acls.is_empty() {
+            log.add_field(fields::SQUID_HTTP_ACCESS_ACLS, SiemField::Text(Cow::Owned(acls)));
+        }
+        Some(description)
+    }
+
+    /// All the ACLs must match. Squid stops at the first one that does not.
+    fn rule_matches(&self, rule: &HttpAccessRule, request: &AccessRequest) -> Option<bool> {
+        let mut unknown = false;
+        for acl_ref in &rule.acls {
+            let matches = if acl_ref.name == "all" && self.config.acl("all").is_none() {
+                Some(true)
+            } else {
+                self.config.acl(&acl_ref.name).and_then(|acl| acl_matches(acl, request))
+            };
+            match matches {
+                Some(matches) if matches == acl_ref.negated => return Some(false),
+                Some(_) => {}
+                None => unknown = true,
+            }
+        }
+        if unknown {
+            None
+        } else {
+            Some(true)
+        }
+    }
+}
+
+/// Whether the request matches the ACL. None if it cannot be evaluated offline.
+pub fn acl_matches(acl: &Acl, request: &AccessRequest) -> Option<bool> {
+    // Values read from a file: `acl blocked dstdomain "/etc/squid/blocked.txt"`
+    if acl.values.iter().any(|v| v.starts_with('"')) {
+        return None;
+    }
+    match &acl.acl_type {
+        AclType::Src => Some(acl.values.iter().any(|v| v == "all" || ip_matches(v, &request.client_ip))),
+        AclType::Dst => request.server_ip.map(|ip| acl.values.iter().any(|v| v == "all" || ip_matches(v, &ip))),
+        AclType::DstDomain => Some(acl.values.iter().any(|v| domain_matches(v, request.domain))),
+        AclType::Port => Some(acl.values.iter().any(|v| port_matches(v, request.port))),
+        AclType::Method => Some(acl.values.iter().any(|v| v.eq_ignore_ascii_case(request.method))),
+        AclType::Proto => Some(acl.values.iter().any(|v| v.eq_ignore_ascii_case(request.protocol))),
+        AclType::ProxyAuth => {
+            if request.user.is_empty() || request.user == "-" {
+                Some(false)
+            } else {
+                Some(acl.values.iter().any(|v| v == "REQUIRED" || v == request.user))
+            }
+        }
+        AclType::Time => Some(acl.entries.iter().any(|entry| time_matches(entry, &request.time))),
+        _ => None,
+    }
+}
+
+/// `.example.com` matches the domain and its subdomains, `example.com` only the domain
+fn domain_matches(entry: &str, domain: &str) -> bool {
+    let domain = domain.trim_end_matches('.');
+    match entry.strip_prefix('.') {
+        Some(parent) => {
+            // Compared as bytes, the offset can fall inside a non ASCII character
+            let (domain, parent) = (domain.as_bytes(), parent.as_bytes());
+            domain.eq_ignore_ascii_case(parent)
+                || (domain.len() > parent.len() + 1
+                    && domain[domain.len() - parent.len()..].eq_ignore_ascii_case(parent)
+                    && domain[domain.len() - parent.len() - 1] == b'.')
+        }
+        None => domain.eq_ignore_ascii_case(entry),
+    }
+}
+
+/// `443` or `1025-65535`
+fn port_matches(entry: &str, port: u16) -> bool {
+    match entry.find('-') {
+        Some(pos) => match (entry[..pos].parse::<u16>(), entry[pos + 1..].parse::<u16>()) {
+            (Ok(start), Ok(end)) => start <= port && port <= end,
+            _ => false,
+        },
+        None => entry.parse::<u16>().map(|v| v == port).unwrap_or(false),
+    }
+}
+
+/// Values of one `acl time` line: `[days] [h1:m1-h2:m2]`. Days: S M T W H F A, D for weekdays. Without days all the days match.
+fn time_matches(values: &[String], time: &NaiveDateTime) -> bool {
+    let (ranges, days): (Vec<&String>, Vec<&String>) = values.iter().partition(|v| v.contains(':'));
+    let day_match = days.is_empty() || days.iter().any(|v| day_matches(v, time.weekday()));
+    let time_match = ranges.is_empty() || ranges.iter().any(|v| time_range_matches(v, &time.time()));
+    day_match && time_match
+}
+
+/// Squid day letters: S M T W H F A, D for Monday to Friday. `MTWHFD` is valid.
+fn day_matches(days: &str, weekday: Weekday) -> bool {
+    let letter = match weekday {
+        Weekday::Sun => 'S',
+        Weekday::Mon => 'M',
+        Weekday::Tue => 'T',
+        Weekday::Wed => 'W',
+        Weekday::Thu => 'H',
+        Weekday::Fri => 'F',
+        Weekday::Sat => 'A',
+    };
+    let working_day = !matches!(weekday, Weekday::Sat | Weekday::Sun);
+    let days = days.to_ascii_uppercase();
+    days.chars().all(|c| "SMTWHFAD".contains(c)) && days.chars().any(|c| c == letter || (c == 'D' && working_day))
+}
+
+fn ip_addr(ip: &SiemIp) -> IpAddr {
+    match ip {
+        SiemIp::V4(ip) => IpAddr::V4(Ipv4Addr::from(*ip)),
+        SiemIp::V6(ip) => IpAddr::V6(Ipv6Addr::from(*ip)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{acl_matches, day_matches, domain_matches, AccessRequest, HttpAccessEvaluator};
+    use chrono::Weekday;
+    use crate::fields;
+    use crate::squid::config::SquidConfig;
+    use usiem::components::common::LogParser;
+    use usiem::events::field::{SiemField, SiemIp};
+    use usiem::events::SiemLog;
+
+    fn evaluator() -> HttpAccessEvaluator {
+        HttpAccessEvaluator::new(include_str!("../../docker/squid.conf").parse::<SquidConfig>().expect("Must work"))
+    }
+
+    fn explain(log: &str) -> (Option<String>, SiemLog) {
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        let mut log = crate::squid::SquidParser::new().parse_log(log).expect("Must work");
+        (evaluator().explain_log(&mut log), log)
+    }
+
+    #[test]
+    fn test_explain_denied_connect() {
+        let (rule, log) = explain("1613260836.628      0 172.17.0.1 TCP_DENIED/403 3900 CONNECT www.example.com:8443 - HIER_NONE/- text/html");
+        assert_eq!(rule, Some("deny CONNECT !SSL_ports".to_string()));
+        assert_eq!(log.field(fields::SQUID_HTTP_ACCESS_RULE), Some(&SiemField::from_str("deny CONNECT !SSL_ports")));
+        assert_eq!(log.field(fields::SQUID_HTTP_ACCESS_LINE), Some(&SiemField::U64(46)));
+        assert_eq!(log.field(fields::SQUID_HTTP_ACCESS_ACLS), Some(&SiemField::from_str("CONNECT,!SSL_ports")));
+    }
+
+    #[test]
+    fn test_explain_denied_requests() {
+        let (rule, _) = explain("1613260836.628      0 172.17.0.1 TCP_DENIED/403 3900 GET http://www.example.com:25/ - HIER_NONE/- text/html");
+        assert_eq!(rule, Some("deny !Safe_ports".to_string()));
+        let (rule, _) = explain("1613260836.628      0 8.8.8.8 TCP_DENIED/403 3900 GET http://www.example.com/ - HIER_NONE/- text/html");
+        assert_eq!(rule, Some("deny all".to_string()));
+        // Allowed by the rules: no explanation
+        let (rule, log) = explain("1613260836.628      0 172.17.0.1 TCP_DENIED/403 3900 GET http://www.example.com/ - HIER_NONE/- text/html");
+        assert_eq!(rule, None);
+        assert_eq!(log.field(fields::SQUID_HTTP_ACCESS_RULE), None);
+        // Not denied
+        let (rule, _) = explain("1613260836.628    287 172.17.0.1 TCP_TUNNEL/200 18353 CONNECT www.google.com:443 - HIER_DIRECT/142.250.184.4 -");
+        assert_eq!(rule, None);
+    }
+
+    #[test]
+    fn test_acl_types() {
+        let content = r#"
+acl office src 10.0.0.0/8
+acl blocked dstdomain .example.com
+acl workhours time MTWHF 08:00-17:00
+acl users proxy_auth alice bob
+acl search url_regex -i google
+http_access deny blocked
+http_access allow office workhours
+http_access allow users
+http_access deny search
+"#;
+        let evaluator = HttpAccessEvaluator::new(content.parse::<SquidConfig>().expect("Must work"));
+        // Monday 2021-02-15 10:00:00
+        let monday = chrono::NaiveDateTime::parse_from_str("2021-02-15 10:00:00", "%Y-%m-%d %H:%M:%S").expect("Must work");
+        let saturday = chrono::NaiveDateTime::parse_from_str("2021-02-13 10:00:00", "%Y-%m-%d %H:%M:%S").expect("Must work");
+        let request = AccessRequest {
+            client_ip: "10.1.2.3".parse().expect("Must work"),
+            server_ip: None,
+            domain: "www.example.com",
+            port: 80,
+            method: "GET",
+            protocol: "http",
+            user: "",
+            time: monday,
+        };
+        assert_eq!(evaluator.evaluate(&request).expect("Must work").description(), "deny blocked");
+        let request = AccessRequest { domain: "notexample.com", ..request };
+        assert_eq!(evaluator.evaluate(&request).expect("Must work").description(), "allow office workhours");
+        let request = AccessRequest { time: saturday, user: "bob", ..request };
+        assert_eq!(evaluator.evaluate(&request).expect("Must work").description(), "allow users");
+        // Depends on the regular expression
+        let request = AccessRequest { user: "", ..request };
+        assert_eq!(evaluator.evaluate(&request), None);
+    }
+
+    #[test]
+    fn test_time_lines() {
+        let config = "acl t time M 08:00-09:00\nacl t time F 17:00-18:00\n".parse::<SquidConfig>().expect("Must work");
+        let acl = config.acl("t").expect("Must work");
+        let at = |time: &str| {
+            let time = chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").expect("Must work");
+            let request = AccessRequest {
+                client_ip: "10.1.2.3".parse().expect("Must work"),
+                server_ip: None,
+                domain: "www.example.com",
+                port: 80,
+                method: "GET",
+                protocol: "http",
+                user: "",
+                time,
+            };
+            acl_matches(acl, &request)
+        };
+        // Monday 2021-02-15 and Friday 2021-02-19
+        assert_eq!(at("2021-02-15 08:30:00"), Some(true));
+        assert_eq!(at("2021-02-19 17:30:00"), Some(true));
+        // The days of a line are not combined with the time of the other
+        assert_eq!(at("2021-02-15 17:30:00"), Some(false));
+        assert_eq!(at("2021-02-19 08:30:00"), Some(false));
+    }
+
+    #[test]
+    fn test_day_matches() {
+        assert!(day_matches("MTWHFD", Weekday::Mon));
+        assert!(day_matches("D", Weekday::Fri));
+        assert!(!day_matches("D", Weekday::Sat));
+        assert!(day_matches("SA", Weekday::Sat));
+        assert!(day_matches("SA", Weekday::Sun));
+        assert!(!day_matches("SA", Weekday::Thu));
+        assert!(day_matches("H", Weekday::Thu));
+        // Letters, not the name of Saturday
+        assert!(day_matches("SAT", Weekday::Tue));
+        assert!(!day_matches("MX", Weekday::Mon));
+    }
+
+    #[test]
+    fn test_domain_matches() {
+        assert!(domain_matches(".example.com", "www.Example.com"));
+        assert!(domain_matches(".example.com", "example.com."));
+        assert!(!domain_matches(".example.com", "notexample.com"));
+        assert!(!domain_matches("example.com", "www.example.com"));
+        assert!(!domain_matches(".b.com", "xé.com"));
+        assert!(domain_matches(".b.com", "é.b.com"));
+    }
+}
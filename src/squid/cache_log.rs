@@ -0,0 +1,355 @@
+use std::borrow::Cow;
+use usiem::components::common::{LogParser, LogParsingError};
+use usiem::events::field::SiemField;
+use usiem::events::field_dictionary;
+use usiem::events::{SiemEvent, SiemLog};
+use crate::fields;
+use crate::squidguard::has_shape;
+use crate::syslog::{split_syslog, syslog_timestamp};
+use crate::timezone::LogTimezone;
+
+/// Severity of a cache.log line, from its prefix
+#[derive(Debug, PartialEq, Clone)]
+pub enum CacheLogSeverity {
+    /// `FATAL: ...` or `assertion failed`. Squid stops after writing it.
+    Fatal,
+    /// `ERROR: ...`, `Error negotiating SSL connection`
+    Error,
+    /// `WARNING: ...`, `SECURITY ALERT: ...`
+    Warning,
+    Info,
+}
+
+impl CacheLogSeverity {
+    pub fn from_message(message: &str) -> CacheLogSeverity {
+        let upper = message.trim_start().to_uppercase();
+        if upper.starts_with("FATAL") || upper.contains("ASSERTION FAILED") || upper.contains("TERMINATED ABNORMALLY") {
+            CacheLogSeverity::Fatal
+        } else if upper.starts_with("ERROR") {
+            CacheLogSeverity::Error
+        } else if upper.starts_with("WARNING") || upper.starts_with("SECURITY ALERT") {
+            CacheLogSeverity::Warning
+        } else {
+            CacheLogSeverity::Info
+        }
+    }
+}
+
+impl std::fmt::Display for CacheLogSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CacheLogSeverity::Fatal => write!(f, "fatal"),
+            CacheLogSeverity::Error => write!(f, "error"),
+            CacheLogSeverity::Warning => write!(f, "warning"),
+            CacheLogSeverity::Info => write!(f, "info"),
+        }
+    }
+}
+
+/// What a cache.log line is about
+#[derive(Debug, PartialEq, Clone)]
+pub enum CacheLogClass {
+    /// `Starting Squid Cache version 4.13 for x86_64-pc-linux-gnu...`
+    Start { version: Option<String> },
+    /// `Accepting HTTP Socket connections at local=[::]:3128 remote=[::] FD 12 flags=9`
+    Ready,
+    /// `Preparing for shutdown after 120 requests`, `Squid Cache (Version 4.13): Exiting normally.`
+    Stop,
+    /// `Reconfiguring Squid Cache (version 4.13)...`
+    Reload,
+    /// Configuration loading and mistakes: `Processing Configuration File: ...`, `Bungled squid.conf line 12`
+    Config,
+    /// Redirector, authenticator and certificate helpers: `WARNING: redirector #Hlpr1 exited`,
+    /// `The redirector helpers are crashing too rapidly, need help!`
+    Helper,
+    /// File descriptors, memory and disk limits: `With 1024 file descriptors available`, `Max Mem  size: 262144 KB`
+    Resources,
+    /// Name resolution: `ipcacheParse: No Address records in response to 'example.local'`
+    Dns,
+    /// TLS and SSL bump: `Error negotiating SSL connection on FD 23`, certificate validation errors
+    Tls,
+    Other,
+}
+
+impl CacheLogClass {
+    pub fn from_message(message: &str) -> CacheLogClass {
+        let lower = message.to_lowercase();
+        if let Some(pos) = lower.find("starting squid cache version ") {
+            let version = message.get(pos + 29..).and_then(|v| v.split(' ').next()).filter(|v| !v.is_empty()).map(|v| v.to_string());
+            return CacheLogClass::Start { version };
+        }
+        if lower.starts_with("accepting ") && lower.contains("connections at") {
+            return CacheLogClass::Ready;
+        }
+        if lower.contains("preparing for shutdown") || lower.contains("shutting down") || lower.contains("exiting normally") || lower.contains("terminated abnormally") {
+            return CacheLogClass::Stop;
+        }
+        if lower.contains("reconfiguring squid cache") {
+            return CacheLogClass::Reload;
+        }
+        if lower.contains("helper") || lower.contains("redirector") || lower.contains("#hlpr") {
+            return CacheLogClass::Helper;
+        }
+        if lower.contains("configuration file") || lower.contains("squid.conf") || lower.contains("bungled") || lower.contains("aclparse") || lower.contains("is a subnetwork of") || lower.contains("unrecognized") {
+            return CacheLogClass::Config;
+        }
+        if lower.contains("file descriptor") || lower.contains("filedescriptor") || lower.contains("max mounted files") || lower.contains("max mem") || lower.contains("max swap") || lower.contains("out of memory") {
+            return CacheLogClass::Resources;
+        }
+        if lower.contains("dns") || lower.contains("ipcache") || lower.contains("fqdncache") || lower.contains("nameserver") {
+            return CacheLogClass::Dns;
+        }
+        if lower.contains("ssl") || lower.contains("tls") || lower.contains("certificate") || lower.contains("x509") || lower.contains("bump") {
+            return CacheLogClass::Tls;
+        }
+        CacheLogClass::Other
+    }
+
+    /// Value for the `event.action` field
+    pub fn action(&self) -> &'static str {
+        match self {
+            CacheLogClass::Start { .. } => "start",
+            CacheLogClass::Ready => "ready",
+            CacheLogClass::Stop => "stop",
+            CacheLogClass::Reload => "reload",
+            CacheLogClass::Config => "config",
+            CacheLogClass::Helper => "helper",
+            CacheLogClass::Resources => "resources",
+            CacheLogClass::Dns => "dns",
+            CacheLogClass::Tls => "tls",
+            CacheLogClass::Other => "info",
+        }
+    }
+}
+
+/// Squid cache.log parser, usable as a uSIEM LogParser component.
+#[derive(Clone)]
+pub struct SquidCacheLogParser {
+    timezone: LogTimezone,
+}
+
+impl Default for SquidCacheLogParser {
+    fn default() -> SquidCacheLogParser {
+        SquidCacheLogParser::new()
+    }
+}
+
+impl SquidCacheLogParser {
+    pub fn new() -> SquidCacheLogParser {
+        SquidCacheLogParser {
+            timezone: LogTimezone::Utc,
+        }
+    }
+    /// Squid writes the cache.log dates in the local time of the proxy. Defaults to UTC.
+    pub fn with_timezone(mut self, timezone: LogTimezone) -> SquidCacheLogParser {
+        self.timezone = timezone;
+        self
+    }
+}
+
+impl LogParser for SquidCacheLogParser {
+    fn parse_log(&self, log: SiemLog) -> Result<SiemLog, LogParsingError> {
+        parse_log_with_timezone(log, &self.timezone)
+    }
+    fn device_match(&self, log: &SiemLog) -> bool {
+        split_header(log.message()).is_some() || split_syslog_message(log.message()).is_some()
+    }
+    fn name(&self) -> &str {
+        "SquidCacheLog"
+    }
+    fn description(&self) -> &str {
+        "Squid cache.log parser for service and health events"
+    }
+}
+
+/// Parses a cache.log line reading the dates as UTC
+pub fn parse_log(log: SiemLog) -> Result<SiemLog, LogParsingError> {
+    parse_log_with_timezone(log, &LogTimezone::Utc)
+}
+
+/// Parses a cache.log line written in the local time of the timezone
+pub fn parse_log_with_timezone(log: SiemLog, timezone: &LogTimezone) -> Result<SiemLog, LogParsingError> {
+    let log_line = log.message();
+    let (event_created, kid, message) = match split_header(log_line) {
+        Some((date, kid, message)) => match timezone.parse_millis(date, "%Y/%m/%d %H:%M:%S%.f") {
+            Some(timestamp) => (timestamp, kid, message),
+            None => return Err(LogParsingError::ParserError(log)),
+        },
+        // Squid started with -s sends cache.log to syslog without its own date
+        None => match (split_syslog_message(log_line), syslog_timestamp(log_line, timezone, log.event_received())) {
+            (Some((kid, message)), Some(timestamp)) => (timestamp, kid, message),
+            (Some(_), None) => return Err(LogParsingError::ParserError(log)),
+            (None, _) => return Err(LogParsingError::NoValidParser(log)),
+        },
+    };
+    let severity = CacheLogSeverity::from_message(message);
+    let class = CacheLogClass::from_message(message);
+    let message = message.to_string();
+    let mut log = SiemLog::new(log_line.to_string(), log.event_received(), log.origin().clone());
+    log.set_event_created(event_created);
+    log.set_event(SiemEvent::Endpoint);
+    log.add_field(field_dictionary::EVENT_ACTION, SiemField::from_str(class.action()));
+    log.add_field(fields::LOG_LEVEL, SiemField::from_str(severity.to_string()));
+    if let Some(kid) = kid {
+        log.add_field(fields::SQUID_KID, SiemField::U64(kid));
+    }
+    if let CacheLogClass::Start { version: Some(version) } = &class {
+        log.add_field(fields::SERVICE_VERSION, SiemField::Text(Cow::Owned(version.to_string())));
+    }
+    match severity {
+        CacheLogSeverity::Fatal | CacheLogSeverity::Error | CacheLogSeverity::Warning => {
+            log.add_field(fields::ERROR_MESSAGE, SiemField::Text(Cow::Owned(message)));
+        }
+        CacheLogSeverity::Info => {}
+    }
+    Ok(log)
+}
+
+/// Splits the `YYYY/MM/DD HH:MM:SS[.mmm] [kidN]| message` header, skipping the syslog header if any.
+/// Returns the date, the kid id (not written by Squid 3.1 and older) and the message.
+pub fn split_header<'a>(log_line: &'a str) -> Option<(&'a str, Option<u64>, &'a str)> {
+    for (pos, _) in log_line.match_indices('|') {
+        let (date_end, kid) = match log_line[..pos].rfind(" kid") {
+            Some(kid_pos) => match log_line[kid_pos + 4..pos].parse::<u64>() {
+                Ok(kid) => (kid_pos, Some(kid)),
+                Err(_) => continue,
+            },
+            None => (pos, None),
+        };
+        // Squid 6 writes milliseconds
+        let seconds_end = match date_end.checked_sub(4).and_then(|start| log_line.get(start..date_end)) {
+            Some(millis) if has_shape(millis, ".ddd") => date_end - 4,
+            _ => date_end,
+        };
+        let date_start = match seconds_end.checked_sub(19) {
+            Some(start) => start,
+            None => continue,
+        };
+        match log_line.get(date_start..seconds_end) {
+            Some(date) if has_shape(date, "dddd/dd/dd dd:dd:dd") => {}
+            _ => continue,
+        }
+        return Some((&log_line[date_start..date_end], kid, log_line[pos + 1..].trim()));
+    }
+    None
+}
+
+/// Beginning of the cache.log messages written without the `kidN|` prefix (Squid 3.1 and older)
+const CACHE_LOG_STARTS: [&str; 12] = [
+    "FATAL:",
+    "ERROR:",
+    "WARNING:",
+    "WARNING!",
+    "SECURITY ALERT:",
+    "assertion failed:",
+    "Error negotiating SSL",
+    "Starting Squid Cache",
+    "Reconfiguring Squid Cache",
+    "Preparing for shutdown",
+    "Squid Cache (Version",
+    "Accepting ",
+];
+
+/// Splits a cache.log line sent to syslog by Squid, without the cache.log date: `squid[26]: kid1| message`.
+/// The access log is also sent with the `squid` tag, so the message must start with `kidN|` or a known
+/// cache.log message. Returns the kid id and the message.
+pub fn split_syslog_message<'a>(log_line: &'a str) -> Option<(Option<u64>, &'a str)> {
+    let (_, message) = split_syslog(log_line, "squid")?;
+    let kid = message.strip_prefix("kid").and_then(|text| {
+        let pos = text.find("|")?;
+        Some((text[..pos].parse::<u64>().ok()?, text[pos + 1..].trim()))
+    });
+    match kid {
+        Some((kid, message)) => Some((Some(kid), message)),
+        None => {
+            let message = message.trim();
+            if CACHE_LOG_STARTS.iter().any(|start| message.starts_with(start)) {
+                Some((None, message))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{split_header, split_syslog_message, CacheLogClass, CacheLogSeverity, SquidCacheLogParser};
+    use crate::fields;
+    use crate::timezone::LogTimezone;
+    use usiem::components::common::LogParser;
+    use usiem::events::field::{SiemField, SiemIp};
+    use usiem::events::field_dictionary;
+    use usiem::events::SiemLog;
+
+    #[test]
+    fn test_split_header() {
+        assert_eq!(split_header("2021/02/14 00:02:33 kid1| Starting Squid Cache version 4.13"), Some(("2021/02/14 00:02:33", Some(1), "Starting Squid Cache version 4.13")));
+        assert_eq!(split_header("2021/02/14 00:02:33| Starting Squid Cache version 3.1.23"), Some(("2021/02/14 00:02:33", None, "Starting Squid Cache version 3.1.23")));
+        assert_eq!(split_header("2021/02/14 00:02:33.125 kid2| ERROR: DNS failure"), Some(("2021/02/14 00:02:33.125", Some(2), "ERROR: DNS failure")));
+        assert_eq!(split_header("<13>Feb 14 00:02:33 proxy squid[26]: 2021/02/14 00:02:33 kid1| WARNING: x | y"), Some(("2021/02/14 00:02:33", Some(1), "WARNING: x | y")));
+        assert_eq!(split_header("2021-02-14 00:02:33 [26] Request(default/porn/-) pornpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT"), None);
+        assert_eq!(split_header("1613260836.628    287 172.17.0.1 TCP_TUNNEL/200 18353 CONNECT www.google.com:443 - HIER_DIRECT/142.250.184.4 -"), None);
+    }
+
+    #[test]
+    fn test_classes() {
+        let cases = [
+            ("Starting Squid Cache version 4.13 for x86_64-pc-linux-gnu...", CacheLogClass::Start { version: Some("4.13".to_string()) }, CacheLogSeverity::Info),
+            ("Accepting HTTP Socket connections at local=[::]:3128 remote=[::] FD 12 flags=9", CacheLogClass::Ready, CacheLogSeverity::Info),
+            ("Preparing for shutdown after 120 requests", CacheLogClass::Stop, CacheLogSeverity::Info),
+            ("Reconfiguring Squid Cache (version 4.13)...", CacheLogClass::Reload, CacheLogSeverity::Info),
+            ("WARNING: The redirector helpers are crashing too rapidly, need help!", CacheLogClass::Helper, CacheLogSeverity::Warning),
+            ("FATAL: The redirector helpers are crashing too rapidly, need help!", CacheLogClass::Helper, CacheLogSeverity::Fatal),
+            ("Max Mounted Files: 1048576", CacheLogClass::Resources, CacheLogSeverity::Info),
+            ("WARNING! Your cache is running out of filedescriptors", CacheLogClass::Resources, CacheLogSeverity::Warning),
+            ("ipcacheParse: No Address records in response to 'example.local'", CacheLogClass::Dns, CacheLogSeverity::Info),
+            ("ERROR: DNS failure while resolving example.local: Name error.", CacheLogClass::Dns, CacheLogSeverity::Error),
+            ("Error negotiating SSL connection on FD 23: error:14094418:SSL routines:ssl3_read_bytes:tlsv1 alert unknown ca (1/0)", CacheLogClass::Tls, CacheLogSeverity::Error),
+            ("FATAL: Bungled /etc/squid/squid.conf line 12: acl localnet src", CacheLogClass::Config, CacheLogSeverity::Fatal),
+            ("Set Current Directory to /var/spool/squid", CacheLogClass::Other, CacheLogSeverity::Info),
+        ];
+        for (message, class, severity) in cases.iter() {
+            assert_eq!(&CacheLogClass::from_message(message), class, "{}", message);
+            assert_eq!(&CacheLogSeverity::from_message(message), severity, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let parser = SquidCacheLogParser::new().with_timezone("+01:00".parse::<LogTimezone>().expect("Must work"));
+        let log = SiemLog::new("2021/02/14 01:02:33 kid1| FATAL: The redirector helpers are crashing too rapidly, need help!".to_string(), 0, SiemIp::V4(0));
+        assert!(parser.device_match(&log));
+        let log = parser.parse_log(log).expect("Must work");
+        assert_eq!(log.event_created(), 1613260953000);
+        assert_eq!(log.field(field_dictionary::EVENT_ACTION), Some(&SiemField::from_str("helper")));
+        assert_eq!(log.field(fields::LOG_LEVEL), Some(&SiemField::from_str("fatal")));
+        assert_eq!(log.field(fields::SQUID_KID), Some(&SiemField::U64(1)));
+        assert_eq!(log.field(fields::ERROR_MESSAGE), Some(&SiemField::from_str("FATAL: The redirector helpers are crashing too rapidly, need help!")));
+
+        let log = SiemLog::new("2021/02/14 01:02:33.500 kid1| Starting Squid Cache version 6.1 for x86_64-pc-linux-gnu...".to_string(), 0, SiemIp::V4(0));
+        let log = parser.parse_log(log).expect("Must work");
+        assert_eq!(log.event_created(), 1613260953500);
+        assert_eq!(log.field(fields::SERVICE_VERSION), Some(&SiemField::from_str("6.1")));
+        assert_eq!(log.field(fields::ERROR_MESSAGE), None);
+
+        // Sent to syslog without the cache.log date. Received on 2021-02-14 00:10:00 UTC
+        let log = SiemLog::new("<11>Feb 14 01:02:33 proxy squid[26]: kid1| ERROR: DNS failure while resolving example.local: Name error.".to_string(), 1613261400000, SiemIp::V4(0));
+        assert!(parser.device_match(&log));
+        let log = parser.parse_log(log).expect("Must work");
+        assert_eq!(log.event_created(), 1613260953000);
+        assert_eq!(log.field(fields::SQUID_KID), Some(&SiemField::U64(1)));
+        assert_eq!(log.field(fields::LOG_LEVEL), Some(&SiemField::from_str("error")));
+    }
+
+    #[test]
+    fn test_split_syslog_message() {
+        assert_eq!(split_syslog_message("<11>Feb 14 01:02:33 proxy squid[26]: kid1| ERROR: DNS failure"), Some((Some(1), "ERROR: DNS failure")));
+        assert_eq!(split_syslog_message("<11>Feb 14 01:02:33 proxy squid[26]: Starting Squid Cache version 3.1.23"), Some((None, "Starting Squid Cache version 3.1.23")));
+        assert_eq!(split_syslog_message("<13>Feb 14 01:02:33 proxy squid[26]: 1613260836.628 287 172.17.0.1 TCP_TUNNEL/200 18353 CONNECT www.google.com:443 - HIER_DIRECT/142.250.184.4 -"), None);
+        assert_eq!(split_syslog_message("<13>Feb 14 01:02:33 proxy squidGuard[26]: Request(default/porn/-) pornpage.com:443 172.17.0.1/- - CONNECT REDIRECT"), None);
+        // Access log lines in the common and combined formats
+        assert_eq!(split_syslog_message("<13>Feb 14 01:02:33 proxy squid[26]: 2001:db8::1 - - [14/Feb/2021:01:02:33 +0100] \"GET http://www.example.com/ HTTP/1.1\" 200 1024 TCP_MISS:HIER_DIRECT"), None);
+        assert_eq!(split_syslog_message("<13>Feb 14 01:02:33 proxy squid[26]: pc1.example.local - alice [14/Feb/2021:01:02:33 +0100] \"GET http://dns.example.com/ HTTP/1.1\" 200 1024 \"-\" \"curl/7.68.0\" TCP_MISS:HIER_DIRECT"), None);
+    }
+}
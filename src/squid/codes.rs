@@ -0,0 +1,416 @@
+use std::borrow::Cow;
+use usiem::events::field::SiemField;
+use usiem::events::webproxy::WebProxyOutcome;
+use usiem::events::SiemLog;
+use crate::fields;
+use super::http_outcome;
+
+/// Protocol used to handle the request: first tag of the result code
+#[derive(Debug, PartialEq, Clone)]
+pub enum SquidProtocol {
+    TCP,
+    UDP,
+    /// The request was not processed, normally because of an error or a denial before any lookup
+    NONE,
+}
+
+impl std::fmt::Display for SquidProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Cache result of the request.
+/// Documentation: https://wiki.squid-cache.org/SquidFaq/SquidLogs#squid-result-codes
+#[allow(non_camel_case_types)]
+#[derive(Debug, PartialEq, Clone)]
+pub enum SquidCacheResult {
+    /// A valid copy of the requested object was in the cache
+    HIT,
+    /// A valid copy of the requested object was in the cache and it was in memory
+    MEM_HIT,
+    /// The response object delivered was the network response object
+    MISS,
+    /// The request was denied by access controls
+    DENIED,
+    /// The reply was denied by access controls (http_reply_access)
+    DENIED_REPLY,
+    /// A CONNECT request was tunneled
+    TUNNEL,
+    /// The request was redirected by a redirector or rewriter helper
+    REDIRECT,
+    /// The ICP request was for a cached object that must not be fetched
+    NOFETCH,
+    /// A negatively cached error response was delivered
+    NEGATIVE_HIT,
+    /// The client sent a revalidation (conditional) request and it was satisfied from the cache
+    IMS_HIT,
+    /// The client sent a If-None-Match request and it was satisfied from the cache
+    INM_HIT,
+    /// The object was served from the cache in offline mode
+    OFFLINE_HIT,
+    /// A stale object was served from the cache
+    STALE_HIT,
+    /// The object was believed to be in the cache, but could not be accessed
+    SWAPFAIL_MISS,
+    /// The cached object was revalidated and the server replied 304 Not Modified
+    REFRESH_UNMODIFIED,
+    /// The cached object was revalidated and the server sent a new object
+    REFRESH_MODIFIED,
+    /// The revalidation failed and the old cached object was delivered
+    REFRESH_FAIL_OLD,
+    /// The revalidation failed and the error was forwarded to the client
+    REFRESH_FAIL_ERR,
+    /// The object was revalidated, without more details
+    REFRESH,
+    /// The client issued a no-cache pragma and the object was fetched again
+    CLIENT_REFRESH_MISS,
+    /// No cache result. Used with the NONE protocol
+    NONE,
+    UNKNOWN(String),
+}
+
+impl SquidCacheResult {
+    pub fn parse(val: &str) -> SquidCacheResult {
+        match val {
+            "HIT" => SquidCacheResult::HIT,
+            "MEM_HIT" => SquidCacheResult::MEM_HIT,
+            "MISS" => SquidCacheResult::MISS,
+            "DENIED" => SquidCacheResult::DENIED,
+            "DENIED_REPLY" => SquidCacheResult::DENIED_REPLY,
+            "TUNNEL" => SquidCacheResult::TUNNEL,
+            "REDIRECT" => SquidCacheResult::REDIRECT,
+            "NOFETCH" => SquidCacheResult::NOFETCH,
+            "NEGATIVE_HIT" => SquidCacheResult::NEGATIVE_HIT,
+            "IMS_HIT" => SquidCacheResult::IMS_HIT,
+            "INM_HIT" => SquidCacheResult::INM_HIT,
+            "OFFLINE_HIT" => SquidCacheResult::OFFLINE_HIT,
+            "STALE_HIT" => SquidCacheResult::STALE_HIT,
+            "SWAPFAIL_MISS" | "SWAPFAIL" => SquidCacheResult::SWAPFAIL_MISS,
+            "REFRESH_UNMODIFIED" | "REFRESH_HIT" => SquidCacheResult::REFRESH_UNMODIFIED,
+            "REFRESH_MODIFIED" | "REFRESH_MISS" => SquidCacheResult::REFRESH_MODIFIED,
+            "REFRESH_FAIL_OLD" | "REF_FAIL_HIT" => SquidCacheResult::REFRESH_FAIL_OLD,
+            "REFRESH_FAIL_ERR" | "REFRESH_FAIL" => SquidCacheResult::REFRESH_FAIL_ERR,
+            "REFRESH" => SquidCacheResult::REFRESH,
+            "CLIENT_REFRESH_MISS" => SquidCacheResult::CLIENT_REFRESH_MISS,
+            "NONE" | "" => SquidCacheResult::NONE,
+            _ => SquidCacheResult::UNKNOWN(val.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for SquidCacheResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SquidCacheResult::UNKNOWN(val) => write!(f, "{}", val),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// Tags appended at the end of the result code
+#[derive(Debug, PartialEq, Clone)]
+pub enum SquidResultSuffix {
+    /// The response was not completed due to the connection being aborted
+    ABORTED,
+    /// The response was not completed due to a connection timeout
+    TIMEDOUT,
+    /// While refreshing a cached response, Squid got an older response and ignored it
+    IGNORED,
+}
+
+impl std::fmt::Display for SquidResultSuffix {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Squid result code like `TCP_REFRESH_UNMODIFIED` or `TCP_TUNNEL_ABORTED`
+#[derive(Debug, PartialEq, Clone)]
+pub struct SquidResultCode {
+    pub protocol: SquidProtocol,
+    pub result: SquidCacheResult,
+    pub suffixes: Vec<SquidResultSuffix>,
+    /// `CF` tag: the request was collapsed with another one
+    pub collapsed: bool,
+}
+
+impl SquidResultCode {
+    pub fn parse(val: &str) -> SquidResultCode {
+        let mut tags: Vec<&str> = val.split('_').filter(|v| !v.is_empty()).collect();
+        let mut suffixes = Vec::new();
+        while let Some(tag) = tags.last() {
+            let suffix = match *tag {
+                "ABORTED" => SquidResultSuffix::ABORTED,
+                "TIMEDOUT" => SquidResultSuffix::TIMEDOUT,
+                "IGNORED" => SquidResultSuffix::IGNORED,
+                _ => break,
+            };
+            suffixes.insert(0, suffix);
+            tags.pop();
+        }
+        let protocol = match tags.first() {
+            Some(&"TCP") => SquidProtocol::TCP,
+            Some(&"UDP") => SquidProtocol::UDP,
+            _ => SquidProtocol::NONE,
+        };
+        if !tags.is_empty() && ["TCP", "UDP", "NONE"].contains(&tags[0]) {
+            tags.remove(0);
+        }
+        let collapsed = tags.contains(&"CF");
+        // ASYNC and CF only describe how the request was processed
+        let result = tags
+            .iter()
+            .filter(|v| **v != "CF" && **v != "ASYNC")
+            .map(|v| *v)
+            .collect::<Vec<&str>>()
+            .join("_");
+        SquidResultCode {
+            protocol,
+            result: SquidCacheResult::parse(&result),
+            suffixes,
+            collapsed,
+        }
+    }
+
+    /// The proxy blocked the request when it was denied by access controls or redirected by a filter.
+    /// Requests not processed by a protocol (NONE) are decided by the HTTP status: ssl-bump splices are
+    /// logged as `NONE/200 CONNECT`, aborted ones as `NONE_NONE/000`. Errors sent by the origin server are allowed traffic.
+    pub fn outcome(&self, http_code: u32) -> WebProxyOutcome {
+        match self.result {
+            SquidCacheResult::DENIED | SquidCacheResult::DENIED_REPLY | SquidCacheResult::REDIRECT => WebProxyOutcome::BLOCK,
+            _ => match self.protocol {
+                SquidProtocol::NONE => http_outcome(http_code),
+                _ => WebProxyOutcome::ALLOW,
+            },
+        }
+    }
+
+    pub fn is_hit(&self) -> bool {
+        match self.result {
+            SquidCacheResult::HIT
+            | SquidCacheResult::MEM_HIT
+            | SquidCacheResult::NEGATIVE_HIT
+            | SquidCacheResult::IMS_HIT
+            | SquidCacheResult::INM_HIT
+            | SquidCacheResult::OFFLINE_HIT
+            | SquidCacheResult::STALE_HIT
+            | SquidCacheResult::REFRESH_UNMODIFIED
+            | SquidCacheResult::REFRESH_FAIL_OLD => true,
+            _ => false,
+        }
+    }
+
+    /// Adds the result code, protocol, cache result and suffixes as log fields
+    pub fn add_fields(&self, log: &mut SiemLog, code: &str) {
+        log.add_field(fields::SQUID_RESULT_CODE, SiemField::Text(Cow::Owned(code.to_string())));
+        log.add_field(fields::SQUID_RESULT_PROTOCOL, SiemField::Text(Cow::Owned(self.protocol.to_string())));
+        log.add_field(fields::SQUID_RESULT_CACHE, SiemField::Text(Cow::Owned(self.result.to_string())));
+        if !self.suffixes.is_empty() {
+            let suffixes: Vec<String> = self.suffixes.iter().map(|v| v.to_string()).collect();
+            log.add_field(fields::SQUID_RESULT_SUFFIX, SiemField::Text(Cow::Owned(suffixes.join(","))));
+        }
+    }
+}
+
+/// Hierarchy code: how and where the request was forwarded.
+/// Documentation: https://wiki.squid-cache.org/SquidFaq/SquidLogs#hierarchy-codes
+#[allow(non_camel_case_types)]
+#[derive(Debug, PartialEq, Clone)]
+pub enum SquidHierarchyCode {
+    /// No hierarchy information. TCP_HIT, TCP_DENIED and errors
+    NONE,
+    /// The object was fetched from the origin server
+    DIRECT,
+    /// The object was fetched from a sibling cache which replied with UDP_HIT
+    SIBLING_HIT,
+    /// The object was requested from a parent cache which replied with UDP_HIT
+    PARENT_HIT,
+    /// No ICP queries were sent. This parent was chosen because it was marked as default
+    DEFAULT_PARENT,
+    /// The object was requested from the only parent appropriate for the given URL
+    SINGLE_PARENT,
+    /// The object was fetched from the first parent in the list of parents
+    FIRSTUP_PARENT,
+    /// The object was fetched from the parent with the fastest possible round trip time
+    FIRST_PARENT_MISS,
+    /// The parent selection was based on the closest parent
+    CLOSEST_PARENT_MISS,
+    /// The parent was selected by the ICMP round trip time
+    CLOSEST_PARENT,
+    /// The object was fetched directly from the origin because it was closer
+    CLOSEST_DIRECT,
+    /// The object could not be requested because of a firewall or because there are no parents
+    NO_DIRECT_FAIL,
+    /// The parent was selected because it was the fastest to reply
+    SOURCE_FASTEST,
+    /// The parent was selected with round robin
+    ROUNDROBIN_PARENT,
+    /// The object was fetched from a parent or sibling selected with a cache digest
+    CACHE_DIGEST_HIT,
+    /// The object was fetched from a parent selected with a cache digest
+    CD_PARENT_HIT,
+    /// The object was fetched from a sibling selected with a cache digest
+    CD_SIBLING_HIT,
+    /// The cache digests did not predict a hit and the object was fetched from the origin
+    NO_CACHE_DIGEST_DIRECT,
+    /// The parent was selected by CARP
+    CARP,
+    /// The parent was selected by the `weighted-round-robin` option
+    ANY_OLD_PARENT,
+    /// The parent was selected by the `userhash` option
+    USERHASH_PARENT,
+    /// The parent was selected by the `sourcehash` option
+    SOURCEHASH_PARENT,
+    /// The server connection was pinned by NTLM or Negotiate authentication
+    PINNED,
+    /// The server connection was limited to the original destination of an intercepted request
+    ORIGINAL_DST,
+    /// The connection was taken from a standby pool
+    STANDBY_POOL,
+    UNKNOWN(String),
+}
+
+impl SquidHierarchyCode {
+    /// Parses the code with or without the `HIER_` and `TIMEOUT_` prefixes
+    pub fn parse(val: &str) -> SquidHierarchyCode {
+        let code = match val.strip_prefix("TIMEOUT_") {
+            Some(v) => v,
+            None => val,
+        };
+        let code = match code.strip_prefix("HIER_") {
+            Some(v) => v,
+            None => code,
+        };
+        match code {
+            "NONE" | "" | "-" => SquidHierarchyCode::NONE,
+            "DIRECT" => SquidHierarchyCode::DIRECT,
+            "SIBLING_HIT" => SquidHierarchyCode::SIBLING_HIT,
+            "PARENT_HIT" => SquidHierarchyCode::PARENT_HIT,
+            "DEFAULT_PARENT" => SquidHierarchyCode::DEFAULT_PARENT,
+            "SINGLE_PARENT" => SquidHierarchyCode::SINGLE_PARENT,
+            "FIRSTUP_PARENT" | "FIRST_UP_PARENT" => SquidHierarchyCode::FIRSTUP_PARENT,
+            "FIRST_PARENT_MISS" => SquidHierarchyCode::FIRST_PARENT_MISS,
+            "CLOSEST_PARENT_MISS" => SquidHierarchyCode::CLOSEST_PARENT_MISS,
+            "CLOSEST_PARENT" => SquidHierarchyCode::CLOSEST_PARENT,
+            "CLOSEST_DIRECT" => SquidHierarchyCode::CLOSEST_DIRECT,
+            "NO_DIRECT_FAIL" => SquidHierarchyCode::NO_DIRECT_FAIL,
+            "SOURCE_FASTEST" => SquidHierarchyCode::SOURCE_FASTEST,
+            "ROUNDROBIN_PARENT" => SquidHierarchyCode::ROUNDROBIN_PARENT,
+            "CACHE_DIGEST_HIT" => SquidHierarchyCode::CACHE_DIGEST_HIT,
+            "CD_PARENT_HIT" => SquidHierarchyCode::CD_PARENT_HIT,
+            "CD_SIBLING_HIT" => SquidHierarchyCode::CD_SIBLING_HIT,
+            "NO_CACHE_DIGEST_DIRECT" => SquidHierarchyCode::NO_CACHE_DIGEST_DIRECT,
+            "CARP" => SquidHierarchyCode::CARP,
+            "ANY_OLD_PARENT" => SquidHierarchyCode::ANY_OLD_PARENT,
+            "USERHASH_PARENT" => SquidHierarchyCode::USERHASH_PARENT,
+            "SOURCEHASH_PARENT" => SquidHierarchyCode::SOURCEHASH_PARENT,
+            "PINNED" => SquidHierarchyCode::PINNED,
+            "ORIGINAL_DST" => SquidHierarchyCode::ORIGINAL_DST,
+            "STANDBY_POOL" => SquidHierarchyCode::STANDBY_POOL,
+            _ => SquidHierarchyCode::UNKNOWN(val.to_string()),
+        }
+    }
+
+    /// The request was sent to a cache peer instead of the origin server
+    pub fn is_peer(&self) -> bool {
+        match self {
+            SquidHierarchyCode::NONE
+            | SquidHierarchyCode::DIRECT
+            | SquidHierarchyCode::CLOSEST_DIRECT
+            | SquidHierarchyCode::NO_CACHE_DIGEST_DIRECT
+            | SquidHierarchyCode::NO_DIRECT_FAIL
+            | SquidHierarchyCode::ORIGINAL_DST
+            | SquidHierarchyCode::PINNED
+            | SquidHierarchyCode::UNKNOWN(_) => false,
+            _ => true,
+        }
+    }
+}
+
+impl std::fmt::Display for SquidHierarchyCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SquidHierarchyCode::UNKNOWN(val) => write!(f, "{}", val),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// Adds the hierarchy code and the server host (when it is not an IP) as log fields.
+/// The host is the cache peer if the code says the request was sent to one, else the origin server.
+pub fn add_hierarchy_fields(log: &mut SiemLog, code: &str, server_host: Option<&str>) {
+    log.add_field(fields::SQUID_HIERARCHY_CODE, SiemField::Text(Cow::Owned(code.to_string())));
+    match server_host {
+        Some(host) if SquidHierarchyCode::parse(code).is_peer() => {
+            log.add_field(fields::SQUID_PEER_HOST, SiemField::Text(Cow::Owned(host.to_string())));
+        }
+        Some(host) => {
+            log.add_field(fields::DESTINATION_DOMAIN, SiemField::Text(Cow::Owned(host.to_lowercase())));
+        }
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SquidCacheResult, SquidHierarchyCode, SquidProtocol, SquidResultCode, SquidResultSuffix};
+    use usiem::events::webproxy::WebProxyOutcome;
+
+    #[test]
+    fn test_result_codes() {
+        let code = SquidResultCode::parse("TCP_TUNNEL_ABORTED");
+        assert_eq!(code.protocol, SquidProtocol::TCP);
+        assert_eq!(code.result, SquidCacheResult::TUNNEL);
+        assert_eq!(code.suffixes, vec![SquidResultSuffix::ABORTED]);
+        assert_eq!(code.outcome(200), WebProxyOutcome::ALLOW);
+
+        let code = SquidResultCode::parse("TCP_REFRESH_UNMODIFIED");
+        assert_eq!(code.result, SquidCacheResult::REFRESH_UNMODIFIED);
+        assert!(code.is_hit());
+        assert_eq!(code.outcome(200), WebProxyOutcome::ALLOW);
+
+        let code = SquidResultCode::parse("TCP_DENIED");
+        assert_eq!(code.result, SquidCacheResult::DENIED);
+        assert_eq!(code.outcome(200), WebProxyOutcome::BLOCK);
+
+        let code = SquidResultCode::parse("TCP_CF_MEM_HIT");
+        assert_eq!(code.result, SquidCacheResult::MEM_HIT);
+        assert!(code.collapsed);
+
+        let code = SquidResultCode::parse("UDP_MISS_TIMEDOUT");
+        assert_eq!(code.protocol, SquidProtocol::UDP);
+        assert_eq!(code.result, SquidCacheResult::MISS);
+        assert_eq!(code.suffixes, vec![SquidResultSuffix::TIMEDOUT]);
+
+        let code = SquidResultCode::parse("NONE");
+        assert_eq!(code.protocol, SquidProtocol::NONE);
+        assert_eq!(code.result, SquidCacheResult::NONE);
+        assert_eq!(code.outcome(0), WebProxyOutcome::BLOCK);
+        // NONE/200 CONNECT: ssl-bump peek and splice
+        assert_eq!(code.outcome(200), WebProxyOutcome::ALLOW);
+        // NONE/503: the server could not be reached
+        assert_eq!(code.outcome(503), WebProxyOutcome::ALLOW);
+        assert_eq!(code.outcome(403), WebProxyOutcome::BLOCK);
+
+        let code = SquidResultCode::parse("NONE_NONE_ABORTED");
+        assert_eq!(code.result, SquidCacheResult::NONE);
+        assert_eq!(code.outcome(0), WebProxyOutcome::BLOCK);
+
+        let code = SquidResultCode::parse("TCP_SOMETHING_NEW");
+        assert_eq!(code.result, SquidCacheResult::UNKNOWN(String::from("SOMETHING_NEW")));
+    }
+
+    #[test]
+    fn test_hierarchy_codes() {
+        assert_eq!(SquidHierarchyCode::parse("HIER_DIRECT"), SquidHierarchyCode::DIRECT);
+        assert_eq!(SquidHierarchyCode::parse("HIER_NONE"), SquidHierarchyCode::NONE);
+        assert_eq!(SquidHierarchyCode::parse("PARENT_HIT"), SquidHierarchyCode::PARENT_HIT);
+        assert_eq!(SquidHierarchyCode::parse("TIMEOUT_FIRSTUP_PARENT"), SquidHierarchyCode::FIRSTUP_PARENT);
+        assert_eq!(SquidHierarchyCode::parse("CD_SIBLING_HIT"), SquidHierarchyCode::CD_SIBLING_HIT);
+        assert_eq!(SquidHierarchyCode::parse("ORIGINAL_DST"), SquidHierarchyCode::ORIGINAL_DST);
+        assert!(SquidHierarchyCode::parse("FIRSTUP_PARENT").is_peer());
+        assert!(!SquidHierarchyCode::parse("HIER_DIRECT").is_peer());
+        assert_eq!(SquidHierarchyCode::parse("HIER_SOMETHING"), SquidHierarchyCode::UNKNOWN(String::from("HIER_SOMETHING")));
+    }
+}
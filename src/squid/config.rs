@@ -0,0 +1,465 @@
+use std::path::Path;
+use super::logformat::LogFormat;
+
+/// Type of a squid.conf `acl`. Documentation: http://www.squid-cache.org/Doc/config/acl/
+#[derive(Debug, PartialEq, Clone)]
+pub enum AclType {
+    /// Client address: `acl localnet src 10.0.0.0/8`
+    Src,
+    /// Server address, resolved from the requested host
+    Dst,
+    /// Requested domain: `acl blocked dstdomain .example.com`
+    DstDomain,
+    /// Client domain, from a reverse lookup
+    SrcDomain,
+    /// Requested port: `acl Safe_ports port 1025-65535`
+    Port,
+    /// Port where the request was received
+    LocalPort,
+    /// Request method: `acl CONNECT method CONNECT`
+    Method,
+    /// URL scheme: `acl manager proto cache_object`
+    Proto,
+    /// Authenticated user names, `REQUIRED` for any user
+    ProxyAuth,
+    /// Days and hours: `acl workhours time MTWHF 08:00-17:00`
+    Time,
+    /// Regular expression over the whole URL
+    UrlRegex,
+    /// Regular expression over the URL path
+    UrlPathRegex,
+    Other(String),
+}
+
+impl AclType {
+    /// Type of an `acl` line. Types not evaluated offline are kept as `Other`.
+    pub fn parse(val: &str) -> AclType {
+        match val {
+            "src" => AclType::Src,
+            "dst" => AclType::Dst,
+            "dstdomain" => AclType::DstDomain,
+            "srcdomain" => AclType::SrcDomain,
+            "port" => AclType::Port,
+            "localport" => AclType::LocalPort,
+            "method" => AclType::Method,
+            "proto" => AclType::Proto,
+            "proxy_auth" => AclType::ProxyAuth,
+            "time" => AclType::Time,
+            "url_regex" => AclType::UrlRegex,
+            "urlpath_regex" => AclType::UrlPathRegex,
+            _ => AclType::Other(val.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for AclType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AclType::Src => write!(f, "src"),
+            AclType::Dst => write!(f, "dst"),
+            AclType::DstDomain => write!(f, "dstdomain"),
+            AclType::SrcDomain => write!(f, "srcdomain"),
+            AclType::Port => write!(f, "port"),
+            AclType::LocalPort => write!(f, "localport"),
+            AclType::Method => write!(f, "method"),
+            AclType::Proto => write!(f, "proto"),
+            AclType::ProxyAuth => write!(f, "proxy_auth"),
+            AclType::Time => write!(f, "time"),
+            AclType::UrlRegex => write!(f, "url_regex"),
+            AclType::UrlPathRegex => write!(f, "urlpath_regex"),
+            AclType::Other(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Named ACL. The `acl` lines with the same name add their values to it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Acl {
+    pub name: String,
+    pub acl_type: AclType,
+    /// Flags before the values, like `-i` or `-n`
+    pub flags: Vec<String>,
+    pub values: Vec<String>,
+    /// Values of each `acl` line. The lines of a `time` ACL are alternatives, not combined with each other
+    pub entries: Vec<Vec<String>>,
+    /// Line of the first definition
+    pub line: usize,
+}
+
+/// ACL used by a rule, negated with `!`
+#[derive(Debug, PartialEq, Clone)]
+pub struct AclRef {
+    pub name: String,
+    pub negated: bool,
+}
+
+impl std::fmt::Display for AclRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.negated {
+            write!(f, "!{}", self.name)
+        } else {
+            write!(f, "{}", self.name)
+        }
+    }
+}
+
+/// `http_access allow|deny acl...` rule. The request must match all the ACLs.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HttpAccessRule {
+    pub allow: bool,
+    pub acls: Vec<AclRef>,
+    pub line: usize,
+}
+
+impl std::fmt::Display for HttpAccessRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", if self.allow { "allow" } else { "deny" })?;
+        for acl in &self.acls {
+            write!(f, " {}", acl)?;
+        }
+        Ok(())
+    }
+}
+
+/// `logformat <name> <specification>`
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogFormatDirective {
+    pub name: String,
+    pub specification: String,
+    pub line: usize,
+}
+
+/// `access_log <module>:<place> [logformat=<name>] [acl...]` or `access_log none`
+#[derive(Debug, PartialEq, Clone)]
+pub struct AccessLogDirective {
+    /// Destination: `daemon:/var/log/squid/access.log`, `stdio:/path`, `syslog:local4.info` or `none`
+    pub destination: String,
+    /// Name of the format, `squid` if not set
+    pub format: String,
+    /// Other `key=value` options: `buffer-size=64KB`, `rotate=10`
+    pub options: Vec<(String, String)>,
+    pub acls: Vec<AclRef>,
+    pub line: usize,
+}
+
+impl AccessLogDirective {
+    /// Path of the file without the module prefix, if written to a file
+    pub fn path(&self) -> Option<&str> {
+        if self.destination == "none" {
+            return None;
+        }
+        match self.destination.find(':') {
+            Some(pos) => match &self.destination[..pos] {
+                "daemon" | "stdio" => Some(&self.destination[pos + 1..]),
+                _ => None,
+            },
+            None => Some(&self.destination),
+        }
+    }
+}
+
+/// Built-in formats that do not need a `logformat` directive
+pub static BUILTIN_FORMATS: [&str; 6] = ["squid", "common", "combined", "referrer", "useragent", "icap_squid"];
+
+/// Typed model of the ACLs, access rules and logging directives of a squid.conf
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SquidConfig {
+    pub acls: Vec<Acl>,
+    /// Rules in the order they are checked
+    pub http_access: Vec<HttpAccessRule>,
+    pub logformats: Vec<LogFormatDirective>,
+    pub access_logs: Vec<AccessLogDirective>,
+    /// Other directives with their arguments, in order
+    pub directives: Vec<(String, String)>,
+}
+
+impl std::str::FromStr for SquidConfig {
+    type Err = String;
+
+    /// Parses the text of a squid.conf. Errors start with the line number.
+    fn from_str(content: &str) -> Result<SquidConfig, String> {
+        let mut config = SquidConfig::default();
+        for (line, text) in logical_lines(content) {
+            let text = text.trim();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+            let (directive, rest) = match text.find(char::is_whitespace) {
+                Some(pos) => (&text[..pos], text[pos..].trim()),
+                None => (text, ""),
+            };
+            if directive == "logformat" {
+                config.add_logformat(line, rest)?;
+                continue;
+            }
+            let rest = strip_comment(rest);
+            let args: Vec<&str> = rest.split_whitespace().collect();
+            match directive {
+                "acl" => config.add_acl(line, &args)?,
+                "http_access" => config.add_http_access(line, &args)?,
+                "access_log" | "cache_access_log" => config.add_access_log(line, &args)?,
+                _ => config.directives.push((directive.to_string(), rest.to_string())),
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl SquidConfig {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SquidConfig, String> {
+        match std::fs::read_to_string(path.as_ref()) {
+            Ok(content) => content.parse::<SquidConfig>(),
+            Err(err) => Err(format!("Cannot read {}: {}", path.as_ref().display(), err)),
+        }
+    }
+
+    pub fn acl(&self, name: &str) -> Option<&Acl> {
+        self.acls.iter().find(|v| v.name == name)
+    }
+
+    /// Arguments of the first directive with that name: `http_port` -> `3128`
+    pub fn directive(&self, name: &str) -> Option<&str> {
+        self.directives.iter().find(|(directive, _)| directive == name).map(|(_, args)| args.as_str())
+    }
+
+    /// Compiled format by name: a `logformat` directive or a built-in format
+    pub fn log_format(&self, name: &str) -> Result<LogFormat, String> {
+        if let Some(directive) = self.logformats.iter().rev().find(|v| v.name == name) {
+            return LogFormat::new(&directive.name, &directive.specification)
+                .map_err(|e| format!("line {}: {}", directive.line, e));
+        }
+        match name {
+            "squid" => Ok(LogFormat::squid()),
+            "common" => Ok(LogFormat::common()),
+            "combined" => Ok(LogFormat::combined()),
+            _ => Err(format!("Unsupported log format {}", name)),
+        }
+    }
+
+    /// Formats of the access logs written to files, to configure the access log parser.
+    /// Returns the path and the format of each log, or why it cannot be parsed: the built-in
+    /// `referrer`, `useragent` and `icap_squid` formats are not supported.
+    pub fn access_log_formats(&self) -> Vec<(String, Result<LogFormat, String>)> {
+        self.access_logs
+            .iter()
+            .filter_map(|access_log| access_log.path().map(|path| (path.to_string(), self.log_format(&access_log.format))))
+            .collect()
+    }
+
+    fn add_acl(&mut self, line: usize, args: &[&str]) -> Result<(), String> {
+        if args.len() < 2 {
+            return Err(format!("line {}: acl needs a name and a type", line));
+        }
+        let name = args[0];
+        let acl_type = AclType::parse(args[1]);
+        let flags: Vec<String> = args[2..].iter().take_while(|v| v.starts_with('-') && v.len() > 1 && !v[1..].starts_with(char::is_numeric)).map(|v| v.to_string()).collect();
+        let values: Vec<String> = args[2 + flags.len()..].iter().map(|v| v.to_string()).collect();
+        match self.acls.iter_mut().find(|v| v.name == name) {
+            Some(acl) => {
+                if acl.acl_type != acl_type {
+                    return Err(format!("line {}: acl {} was defined as {} in line {}", line, name, acl.acl_type, acl.line));
+                }
+                acl.values.extend(values.iter().cloned());
+                acl.entries.push(values);
+                for flag in flags {
+                    if !acl.flags.contains(&flag) {
+                        acl.flags.push(flag);
+                    }
+                }
+            }
+            None => self.acls.push(Acl {
+                name: name.to_string(),
+                acl_type,
+                flags,
+                values: values.clone(),
+                entries: vec![values],
+                line,
+            }),
+        }
+        Ok(())
+    }
+
+    fn add_http_access(&mut self, line: usize, args: &[&str]) -> Result<(), String> {
+        let allow = match args.first() {
+            Some(&"allow") => true,
+            Some(&"deny") => false,
+            Some(action) => return Err(format!("line {}: http_access action must be allow or deny, not {}", line, action)),
+            None => return Err(format!("line {}: http_access needs an action", line)),
+        };
+        let acls = self.acl_refs(line, &args[1..])?;
+        self.http_access.push(HttpAccessRule { allow, acls, line });
+        Ok(())
+    }
+
+    fn add_logformat(&mut self, line: usize, rest: &str) -> Result<(), String> {
+        let (name, specification) = match rest.find(char::is_whitespace) {
+            Some(pos) => (&rest[..pos], rest[pos..].trim()),
+            None => return Err(format!("line {}: logformat needs a name and a specification", line)),
+        };
+        self.logformats.push(LogFormatDirective {
+            name: name.to_string(),
+            specification: specification.to_string(),
+            line,
+        });
+        Ok(())
+    }
+
+    fn add_access_log(&mut self, line: usize, args: &[&str]) -> Result<(), String> {
+        let destination = match args.first() {
+            Some(destination) => destination.to_string(),
+            None => return Err(format!("line {}: access_log needs a destination", line)),
+        };
+        let mut format = None;
+        let mut options = Vec::new();
+        let mut acl_args = Vec::new();
+        for (i, arg) in args[1..].iter().enumerate() {
+            match arg.find('=') {
+                Some(pos) if &arg[..pos] == "logformat" => format = Some(arg[pos + 1..].to_string()),
+                Some(pos) => options.push((arg[..pos].to_string(), arg[pos + 1..].to_string())),
+                // Old syntax: `access_log <path> <format> [acl...]`
+                None if i == 0 && format.is_none() && self.is_format(arg) => format = Some(arg.to_string()),
+                None => acl_args.push(*arg),
+            }
+        }
+        let acls = self.acl_refs(line, &acl_args)?;
+        self.access_logs.push(AccessLogDirective {
+            destination,
+            format: format.unwrap_or_else(|| "squid".to_string()),
+            options,
+            acls,
+            line,
+        });
+        Ok(())
+    }
+
+    fn is_format(&self, name: &str) -> bool {
+        BUILTIN_FORMATS.contains(&name) || self.logformats.iter().any(|v| v.name == name)
+    }
+
+    fn acl_refs(&self, line: usize, args: &[&str]) -> Result<Vec<AclRef>, String> {
+        let mut acls = Vec::with_capacity(args.len());
+        for arg in args {
+            let (negated, name) = match arg.strip_prefix('!') {
+                Some(name) => (true, name),
+                None => (false, *arg),
+            };
+            // `all` is predefined since Squid 3
+            if name != "all" && self.acl(name).is_none() {
+                return Err(format!("line {}: unknown acl {}", line, name));
+            }
+            acls.push(AclRef { name: name.to_string(), negated });
+        }
+        Ok(acls)
+    }
+}
+
+/// Lines joined when they end with `\`, with the number of their first line
+fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (number, line) in content.lines().enumerate() {
+        let (start, mut text) = match current.take() {
+            Some((start, text)) => (start, text),
+            None => (number + 1, String::new()),
+        };
+        match line.strip_suffix('\\') {
+            Some(line) => {
+                text.push_str(line);
+                current = Some((start, text));
+            }
+            None => {
+                text.push_str(line);
+                lines.push((start, text));
+            }
+        }
+    }
+    if let Some(line) = current {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Removes the comments written after the arguments: `acl localnet src 10.0.0.0/8 # RFC1918`
+fn strip_comment(text: &str) -> &str {
+    let mut previous = ' ';
+    for (pos, c) in text.char_indices() {
+        if c == '#' && previous.is_whitespace() {
+            return text[..pos].trim_end();
+        }
+        previous = c;
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AclRef, AclType, SquidConfig};
+
+    #[test]
+    fn test_docker_config() {
+        let config = include_str!("../../docker/squid.conf").parse::<SquidConfig>().expect("Must work");
+        let localnet = config.acl("localnet").expect("Must work");
+        assert_eq!(localnet.acl_type, AclType::Src);
+        assert_eq!(localnet.values, vec!["10.0.0.0/8", "172.16.0.0/12", "192.168.0.0/16", "fc00::/7", "fe80::/10"]);
+        let safe_ports = config.acl("Safe_ports").expect("Must work");
+        assert_eq!(safe_ports.acl_type, AclType::Port);
+        assert_eq!(safe_ports.values.len(), 10);
+        assert!(safe_ports.values.contains(&"1025-65535".to_string()));
+        assert_eq!(config.acl("CONNECT").expect("Must work").acl_type, AclType::Method);
+        assert_eq!(config.acl("manager").expect("Must work").acl_type, AclType::Proto);
+        assert_eq!(config.http_access.len(), 7);
+        assert_eq!(config.http_access[1].to_string(), "deny CONNECT !SSL_ports");
+        assert_eq!(config.http_access[1].acls[1], AclRef { name: "SSL_ports".to_string(), negated: true });
+        assert_eq!(config.http_access[6].to_string(), "deny all");
+        assert_eq!(config.directive("http_port"), Some("3128"));
+        assert_eq!(config.directive("redirect_program"), Some("/usr/bin/squidGuard -c /etc/squidguard/squidGuard.conf"));
+        assert!(config.access_logs.is_empty());
+    }
+
+    #[test]
+    fn test_logging_directives() {
+        let content = r#"
+acl blocked dstdomain -i .example.com \
+    .example.org
+acl workhours time MTWHF 08:00-17:00
+acl search url_regex -i ^https?://(www\.)?google\.
+acl users proxy_auth REQUIRED
+logformat custom %ts.%03tu %>a %Ss/%03>Hs %rm %ru "%{User-Agent}>h" # not a comment
+access_log daemon:/var/log/squid/access.log logformat=custom buffer-size=64KB !workhours
+access_log /var/log/squid/combined.log combined
+access_log /var/log/squid/useragent.log useragent
+access_log syslog:local4.info
+access_log none blocked
+"#;
+        let config = content.parse::<SquidConfig>().expect("Must work");
+        let blocked = config.acl("blocked").expect("Must work");
+        assert_eq!(blocked.flags, vec!["-i"]);
+        assert_eq!(blocked.values, vec![".example.com", ".example.org"]);
+        assert_eq!(config.acl("workhours").expect("Must work").values, vec!["MTWHF", "08:00-17:00"]);
+        assert_eq!(config.acl("search").expect("Must work").acl_type, AclType::UrlRegex);
+        assert_eq!(config.acl("users").expect("Must work").acl_type, AclType::ProxyAuth);
+        assert_eq!(config.logformats[0].specification, "%ts.%03tu %>a %Ss/%03>Hs %rm %ru \"%{User-Agent}>h\" # not a comment");
+        assert_eq!(config.access_logs.len(), 5);
+        assert_eq!(config.access_logs[0].format, "custom");
+        assert_eq!(config.access_logs[0].options, vec![("buffer-size".to_string(), "64KB".to_string())]);
+        assert_eq!(config.access_logs[0].acls, vec![AclRef { name: "workhours".to_string(), negated: true }]);
+        assert_eq!(config.access_logs[1].format, "combined");
+        assert_eq!(config.access_logs[3].path(), None);
+        assert_eq!(config.access_logs[4].acls[0].name, "blocked");
+        let formats = config.access_log_formats();
+        assert_eq!(formats.len(), 3);
+        assert_eq!(formats[0].0, "/var/log/squid/access.log");
+        assert_eq!(formats[0].1.as_ref().map(|v| v.name()), Ok("custom"));
+        assert_eq!(formats[1].1.as_ref().map(|v| v.name()), Ok("combined"));
+        // An unsupported format does not hide the others
+        assert_eq!(formats[2].0, "/var/log/squid/useragent.log");
+        assert_eq!(formats[2].1.as_ref().map(|v| v.name()), Err(&"Unsupported log format useragent".to_string()));
+    }
+
+    #[test]
+    fn test_config_errors() {
+        assert_eq!("acl a src 10.0.0.0/8\nhttp_access allow b".parse::<SquidConfig>().err(), Some("line 2: unknown acl b".to_string()));
+        assert_eq!("acl a src 10.0.0.0/8\nacl a port 80".parse::<SquidConfig>().err(), Some("line 2: acl a was defined as src in line 1".to_string()));
+        assert_eq!("http_access permit all".parse::<SquidConfig>().err(), Some("line 1: http_access action must be allow or deny, not permit".to_string()));
+    }
+}
@@ -0,0 +1,994 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+use chrono::DateTime;
+use usiem::components::common::{LogParser, LogParsingError};
+use usiem::events::common::{HttpMethod, WebProtocol};
+use usiem::events::field::{SiemField, SiemIp};
+use usiem::events::field_dictionary;
+use usiem::events::webproxy::WebProxyEvent;
+use usiem::events::{SiemEvent, SiemLog};
+use super::codes::{add_hierarchy_fields, SquidResultCode};
+use super::{add_byte_fields, add_url_fields, http_outcome, parse_epoch_millis, parse_protocol, parse_url, resolve_hostname, saturate_bytes, HostTable};
+use crate::categorizer::DomainCategorizer;
+use crate::fields;
+use crate::syslog::{split_syslog, syslog_timestamp};
+use crate::timezone::LogTimezone;
+
+/// Native Squid format: `logformat squid %ts.%03tu %6tr %>a %Ss/%03>Hs %<st %rm %ru %[un %Sh/%<a %mt`
+pub static SQUID_FORMAT: &str = "%ts.%03tu %6tr %>a %Ss/%03>Hs %<st %rm %ru %[un %Sh/%<a %mt";
+
+/// Built-in `common` format: `logformat common %>a %[ui %[un [%tl] "%rm %ru HTTP/%rv" %>Hs %<st %Ss:%Sh`
+pub static COMMON_FORMAT: &str = "%>a %[ui %[un [%tl] \"%rm %ru HTTP/%rv\" %>Hs %<st %Ss:%Sh";
+
+/// Built-in `combined` format: `common` with the referer and user agent
+pub static COMBINED_FORMAT: &str = "%>a %[ui %[un [%tl] \"%rm %ru HTTP/%rv\" %>Hs %<st \"%{Referer}>h\" \"%{User-Agent}>h\" %Ss:%Sh";
+
+/// Format codes supported in `logformat` specifications.
+/// Documentation: http://www.squid-cache.org/Doc/config/logformat/
+#[derive(Debug, PartialEq, Clone)]
+pub enum FormatCode {
+    /// `%>a` Client source IP address
+    ClientIp,
+    /// `%>A` Client FQDN
+    ClientFqdn,
+    /// `%>p` Client source port
+    ClientPort,
+    /// `%>la` or `%la` Local IP address the client connected to
+    LocalIp,
+    /// `%>lp` or `%lp` Local port number the client connected to
+    LocalPort,
+    /// `%<a` Server IP address of the last server or peer connection
+    ServerIp,
+    /// `%<A` Server FQDN or peer name
+    ServerFqdn,
+    /// `%<p` Server port number of the last server or peer connection
+    ServerPort,
+    /// `%<la` Local IP address of the last server or peer connection
+    ServerLocalIp,
+    /// `%<lp` Local port number of the last server or peer connection
+    ServerLocalPort,
+    /// `%ts` Seconds since epoch
+    Timestamp,
+    /// `%tu` Subsecond time (milliseconds)
+    TimestampMillis,
+    /// `%tl` Local time. Format: `14/Feb/2021:00:00:36 +0100`
+    LocalTime,
+    /// `%tg` GMT time. Format: `14/Feb/2021:00:00:36 +0000`
+    GmtTime,
+    /// `%tr` Response time (milliseconds)
+    ResponseTime,
+    /// `%dt` Total time spent making DNS lookups (milliseconds)
+    DnsTime,
+    /// `%rm` or `%>rm` Request method
+    Method,
+    /// `%ru` or `%>ru` Request URL
+    Url,
+    /// `%rp` or `%>rp` Request URL path excluding hostname
+    UrlPath,
+    /// `%>rs` Request URL scheme
+    UrlScheme,
+    /// `%>rd` Request URL domain
+    UrlDomain,
+    /// `%>rP` Request URL port
+    UrlPort,
+    /// `%rv` or `%>rv` Request protocol version
+    ProtocolVersion,
+    /// `%>Hs` HTTP status code sent to the client
+    ClientStatus,
+    /// `%<Hs` HTTP status code received from the next hop
+    ServerStatus,
+    /// `%<st` Total size of reply sent to client (after adaptation)
+    ReplySize,
+    /// `%>st` Total size of request received from client
+    RequestSize,
+    /// `%st` Total size of request + reply traffic with client
+    TotalSize,
+    /// `%Ss` Squid request status (TCP_MISS etc)
+    SquidStatus,
+    /// `%Sh` Squid hierarchy status (DEFAULT_PARENT etc)
+    HierarchyStatus,
+    /// `%un` User name (any available)
+    UserName,
+    /// `%ul` User name from authentication
+    UserLogin,
+    /// `%ui` User name from ident
+    UserIdent,
+    /// `%us` User name from SSL
+    UserSsl,
+    /// `%ue` User name from external acl helper
+    UserExternal,
+    /// `%mt` MIME content type
+    MimeType,
+    /// `%>h` Original received request header. Use `%{Header}>h` to select a header.
+    RequestHeader,
+    /// `%<h` Reply header. Use `%{Header}<h` to select a header.
+    ReplyHeader,
+    /// `%>ha` Request header after adaptation and redirection
+    AdaptedRequestHeader,
+    /// `%<ha` Reply header after adaptation
+    AdaptedReplyHeader,
+    /// `%note` Transaction annotations
+    Note,
+}
+
+/// Known codes. Longer codes must go before their prefixes.
+const FORMAT_CODES: &[(&str, FormatCode)] = &[
+    (">la", FormatCode::LocalIp),
+    (">lp", FormatCode::LocalPort),
+    ("<la", FormatCode::ServerLocalIp),
+    ("<lp", FormatCode::ServerLocalPort),
+    (">rm", FormatCode::Method),
+    (">ru", FormatCode::Url),
+    (">rp", FormatCode::UrlPath),
+    (">rs", FormatCode::UrlScheme),
+    (">rd", FormatCode::UrlDomain),
+    (">rP", FormatCode::UrlPort),
+    (">rv", FormatCode::ProtocolVersion),
+    (">Hs", FormatCode::ClientStatus),
+    ("<Hs", FormatCode::ServerStatus),
+    ("<st", FormatCode::ReplySize),
+    (">st", FormatCode::RequestSize),
+    ("note", FormatCode::Note),
+    (">a", FormatCode::ClientIp),
+    (">A", FormatCode::ClientFqdn),
+    (">p", FormatCode::ClientPort),
+    ("<a", FormatCode::ServerIp),
+    ("<A", FormatCode::ServerFqdn),
+    ("<p", FormatCode::ServerPort),
+    (">ha", FormatCode::AdaptedRequestHeader),
+    ("<ha", FormatCode::AdaptedReplyHeader),
+    (">h", FormatCode::RequestHeader),
+    ("<h", FormatCode::ReplyHeader),
+    ("la", FormatCode::LocalIp),
+    ("lp", FormatCode::LocalPort),
+    ("ts", FormatCode::Timestamp),
+    ("tu", FormatCode::TimestampMillis),
+    ("tl", FormatCode::LocalTime),
+    ("tg", FormatCode::GmtTime),
+    ("tr", FormatCode::ResponseTime),
+    ("dt", FormatCode::DnsTime),
+    ("rm", FormatCode::Method),
+    ("ru", FormatCode::Url),
+    ("rp", FormatCode::UrlPath),
+    ("rv", FormatCode::ProtocolVersion),
+    ("Hs", FormatCode::ClientStatus),
+    ("st", FormatCode::TotalSize),
+    ("Ss", FormatCode::SquidStatus),
+    ("Sh", FormatCode::HierarchyStatus),
+    ("un", FormatCode::UserName),
+    ("ul", FormatCode::UserLogin),
+    ("ui", FormatCode::UserIdent),
+    ("us", FormatCode::UserSsl),
+    ("ue", FormatCode::UserExternal),
+    ("mt", FormatCode::MimeType),
+];
+
+/// How the value of a field is encoded in the log line
+#[derive(Debug, PartialEq, Clone)]
+pub enum FieldEncoding {
+    /// Squid default encoding
+    Default,
+    /// `"` Quoted string with backslash escapes
+    Quoted,
+    /// `[` Custom Squid escaping with backslash
+    SquidEscaped,
+    /// `#` URL encoding
+    UrlEncoded,
+    /// `'` Raw value, no encoding
+    Raw,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FormatField {
+    pub code: FormatCode,
+    /// Argument between braces, like the header name in `%{User-Agent}>h`
+    pub argument: Option<String>,
+    pub encoding: FieldEncoding,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum FormatToken {
+    /// Text copied as is to the log line
+    Literal(String),
+    Field(FormatField),
+}
+
+/// Compiled `logformat` specification. Extracts the fields of the access log lines written with it.
+#[derive(Debug, Clone)]
+pub struct LogFormat {
+    name: String,
+    tokens: Vec<FormatToken>,
+    hostnames: Option<HostTable>,
+    query_params: bool,
+    categorizer: Option<Arc<DomainCategorizer>>,
+    timezone: LogTimezone,
+}
+
+impl LogFormat {
+    /// Compiles a format specification like `%>a %[ui %[un [%tl] "%rm %ru HTTP/%rv" %>Hs %<st`
+    pub fn new(name: &str, specification: &str) -> Result<LogFormat, String> {
+        Ok(LogFormat {
+            name: name.to_string(),
+            tokens: compile(specification)?,
+            hostnames: None,
+            query_params: false,
+            categorizer: None,
+            timezone: LogTimezone::Utc,
+        })
+    }
+
+    /// Emit the decoded query parameters as a JSON object in the `url.query_params` field
+    pub fn with_query_params(mut self) -> LogFormat {
+        self.query_params = true;
+        self
+    }
+
+    /// Accept hostnames in the client and server address columns (`log_fqdn on` or `%>A`).
+    /// The hostnames are resolved with the table, if not found the IP is left as 0.0.0.0
+    pub fn with_fqdn(mut self, hostnames: HostTable) -> LogFormat {
+        self.hostnames = Some(hostnames);
+        self
+    }
+
+    /// Sets the rule name and category of the requests using squidGuard domain lists
+    pub fn with_categorizer(mut self, categorizer: Arc<DomainCategorizer>) -> LogFormat {
+        self.categorizer = Some(categorizer);
+        self
+    }
+
+    /// Timezone of the syslog header, the event date of the formats without time codes. Defaults to UTC.
+    pub fn with_timezone(mut self, timezone: LogTimezone) -> LogFormat {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Compiles a `logformat` directive: `logformat <name> <specification>`
+    pub fn from_directive(directive: &str) -> Result<LogFormat, String> {
+        let directive = directive.trim();
+        let directive = match directive.strip_prefix("logformat") {
+            Some(v) => v.trim_start(),
+            None => return Err(String::from("Not a logformat directive")),
+        };
+        match directive.find(|c: char| c.is_whitespace()) {
+            Some(pos) => LogFormat::new(&directive[..pos], directive[pos..].trim()),
+            None => Err(String::from("Missing logformat specification")),
+        }
+    }
+
+    /// The native `squid` format
+    pub fn squid() -> LogFormat {
+        LogFormat::new("squid", SQUID_FORMAT).expect("Native format must compile")
+    }
+
+    /// The built-in `common` format
+    pub fn common() -> LogFormat {
+        LogFormat::new("common", COMMON_FORMAT).expect("Common format must compile")
+    }
+
+    /// The built-in `combined` format
+    pub fn combined() -> LogFormat {
+        LogFormat::new("combined", COMBINED_FORMAT).expect("Combined format must compile")
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn tokens(&self) -> &Vec<FormatToken> {
+        &self.tokens
+    }
+
+    /// Extracts the values of each field of the format. Fields with value `-` are omitted.
+    pub fn extract<'a>(&'a self, text: &'a str) -> Result<Vec<(&'a FormatField, Cow<'a, str>)>, &'static str> {
+        let mut values = Vec::with_capacity(self.tokens.len());
+        let mut pos = 0;
+        for (i, token) in self.tokens.iter().enumerate() {
+            let rest = &text[pos..];
+            match token {
+                FormatToken::Literal(literal) => {
+                    if literal.trim().is_empty() {
+                        let trimmed = rest.trim_start();
+                        if trimmed.len() == rest.len() {
+                            return Err("Expected whitespace");
+                        }
+                        pos += rest.len() - trimmed.len();
+                    } else if rest.starts_with(&literal[..]) {
+                        pos += literal.len();
+                    } else {
+                        return Err("Log does not match the format");
+                    }
+                }
+                FormatToken::Field(field) => {
+                    let (value, len) = match self.tokens.get(i + 1) {
+                        _ if rest.starts_with("\"") && field.encoding == FieldEncoding::Quoted => {
+                            match quoted_end(&rest[1..]) {
+                                Some(end) => (unescape(&rest[1..end + 1]), end + 2),
+                                None => return Err("Unterminated quoted field"),
+                            }
+                        }
+                        Some(FormatToken::Literal(next)) if next.starts_with("\"") => {
+                            // Value enclosed by quotes in the format: "%{Referer}>h"
+                            match quoted_end(rest) {
+                                Some(end) => (unescape(&rest[..end]), end),
+                                None => return Err("Unterminated quoted field"),
+                            }
+                        }
+                        Some(FormatToken::Literal(next)) => {
+                            let end = if next.trim().is_empty() {
+                                rest.find(|c: char| c.is_whitespace())
+                            } else {
+                                rest.find(&next[..])
+                            };
+                            match end {
+                                Some(end) => (Cow::Borrowed(&rest[..end]), end),
+                                None => return Err("Log does not match the format"),
+                            }
+                        }
+                        Some(FormatToken::Field(_)) => {
+                            let end = rest.find(|c: char| c.is_whitespace()).unwrap_or(rest.len());
+                            (Cow::Borrowed(&rest[..end]), end)
+                        }
+                        None => (Cow::Borrowed(rest.trim_end()), rest.len()),
+                    };
+                    pos += len;
+                    let value = match field.encoding {
+                        FieldEncoding::UrlEncoded => match value {
+                            Cow::Borrowed(v) => url_decode(v),
+                            Cow::Owned(v) => Cow::Owned(url_decode(&v).into_owned()),
+                        },
+                        FieldEncoding::SquidEscaped => match value {
+                            Cow::Borrowed(v) => unescape(v),
+                            Cow::Owned(v) => Cow::Owned(unescape(&v).into_owned()),
+                        },
+                        _ => value,
+                    };
+                    let value = match value {
+                        Cow::Borrowed(v) => Cow::Borrowed(v.trim()),
+                        Cow::Owned(v) => Cow::Owned(v.trim().to_string()),
+                    };
+                    if value != "-" && !value.is_empty() {
+                        values.push((field, value));
+                    }
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    pub fn parse_log(&self, log: SiemLog) -> Result<SiemLog, LogParsingError> {
+        let log_line = log.message();
+        let (log_content, syslog_created) = if log_line.starts_with("<") {
+            match split_syslog(log_line, "squid") {
+                // Used as the event date by the formats without time codes
+                Some((_, message)) => (message, syslog_timestamp(log_line, &self.timezone, log.event_received())),
+                None => return Err(LogParsingError::NoValidParser(log)),
+            }
+        } else {
+            (log_line, None)
+        };
+        let values = match self.extract(log_content) {
+            Ok(values) => values,
+            Err(_) => return Err(LogParsingError::NoValidParser(log)),
+        };
+        let mut format_values = FormatValues::default();
+        let mut extra_fields: Vec<(String, String)> = Vec::new();
+        for (field, value) in values {
+            match field.code {
+                FormatCode::ClientIp => format_values.source_ip = Some(value),
+                FormatCode::ClientFqdn => format_values.source_domain = Some(value),
+                FormatCode::ServerIp => format_values.destination_ip = Some(value),
+                FormatCode::Timestamp => format_values.timestamp = Some(value),
+                FormatCode::TimestampMillis => format_values.timestamp_millis = Some(value),
+                FormatCode::LocalTime | FormatCode::GmtTime => format_values.date = Some(value),
+                FormatCode::ResponseTime => format_values.duration = Some(value),
+                FormatCode::Method => format_values.method = Some(value),
+                FormatCode::Url => format_values.url = Some(value),
+                FormatCode::UrlDomain => format_values.url_domain = Some(value),
+                FormatCode::UrlPort => format_values.url_port = Some(value),
+                FormatCode::UrlScheme => format_values.url_scheme = Some(value),
+                FormatCode::ClientStatus => format_values.http_code = Some(value),
+                FormatCode::ReplySize => format_values.in_bytes = Some(value),
+                FormatCode::RequestSize => format_values.out_bytes = Some(value),
+                FormatCode::TotalSize => format_values.total_bytes = Some(value),
+                FormatCode::SquidStatus => format_values.squid_status = Some(value),
+                FormatCode::HierarchyStatus => format_values.hierarchy = Some(value),
+                FormatCode::ServerFqdn => format_values.peer_host = Some(value),
+                FormatCode::UserName
+                | FormatCode::UserLogin
+                | FormatCode::UserExternal
+                | FormatCode::UserSsl
+                | FormatCode::UserIdent => {
+                    if format_values.user_name.is_none() {
+                        format_values.user_name = Some(value)
+                    }
+                }
+                FormatCode::MimeType => format_values.mime_type = Some(value),
+                FormatCode::RequestHeader | FormatCode::ReplyHeader => {
+                    let header = match &field.argument {
+                        Some(arg) => arg.to_lowercase(),
+                        None => continue,
+                    };
+                    let field_name = match (&field.code, &header[..]) {
+                        (FormatCode::RequestHeader, "user-agent") => fields::USER_AGENT_ORIGINAL.to_string(),
+                        (FormatCode::RequestHeader, "referer") => fields::HTTP_REQUEST_REFERRER.to_string(),
+                        (FormatCode::RequestHeader, _) => format!("{}.{}", fields::HTTP_REQUEST_HEADERS, header),
+                        _ => format!("{}.{}", fields::HTTP_RESPONSE_HEADERS, header),
+                    };
+                    extra_fields.push((field_name, value.to_string()));
+                }
+                _ => {}
+            }
+        }
+        let mut log = match format_values.to_log(&log, log_content, syslog_created, self.hostnames.as_ref(), self.query_params) {
+            Ok(log) => log,
+            Err(true) => return Err(LogParsingError::NoValidParser(log)),
+            Err(false) => return Err(LogParsingError::ParserError(log)),
+        };
+        for (field_name, value) in extra_fields {
+            log.add_field(&field_name, SiemField::Text(Cow::Owned(value)));
+        }
+        if let Some(categorizer) = &self.categorizer {
+            categorizer.categorize_log(&mut log);
+        }
+        Ok(log)
+    }
+}
+
+impl LogParser for LogFormat {
+    fn parse_log(&self, log: SiemLog) -> Result<SiemLog, LogParsingError> {
+        LogFormat::parse_log(self, log)
+    }
+    fn device_match(&self, log: &SiemLog) -> bool {
+        let log_line = log.message();
+        let log_content = if log_line.starts_with("<") {
+            match split_syslog(log_line, "squid") {
+                Some((_, message)) => message,
+                None => return false,
+            }
+        } else {
+            log_line
+        };
+        self.extract(log_content).is_ok()
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn description(&self) -> &str {
+        "Squid access log parser for custom logformat specifications"
+    }
+}
+
+/// Values extracted from a log line used to build the WebProxy event
+#[derive(Default)]
+struct FormatValues<'a> {
+    source_ip: Option<Cow<'a, str>>,
+    source_domain: Option<Cow<'a, str>>,
+    destination_ip: Option<Cow<'a, str>>,
+    timestamp: Option<Cow<'a, str>>,
+    timestamp_millis: Option<Cow<'a, str>>,
+    date: Option<Cow<'a, str>>,
+    duration: Option<Cow<'a, str>>,
+    method: Option<Cow<'a, str>>,
+    url: Option<Cow<'a, str>>,
+    url_domain: Option<Cow<'a, str>>,
+    url_port: Option<Cow<'a, str>>,
+    url_scheme: Option<Cow<'a, str>>,
+    http_code: Option<Cow<'a, str>>,
+    in_bytes: Option<Cow<'a, str>>,
+    out_bytes: Option<Cow<'a, str>>,
+    total_bytes: Option<Cow<'a, str>>,
+    squid_status: Option<Cow<'a, str>>,
+    hierarchy: Option<Cow<'a, str>>,
+    peer_host: Option<Cow<'a, str>>,
+    user_name: Option<Cow<'a, str>>,
+    mime_type: Option<Cow<'a, str>>,
+}
+
+impl<'a> FormatValues<'a> {
+    /// Builds the log. The error is true when the log does not belong to this parser.
+    fn to_log(&self, log: &SiemLog, log_content: &str, syslog_created: Option<i64>, hostnames: Option<&HostTable>, query_params: bool) -> Result<SiemLog, bool> {
+        let (source_ip, source_domain) = match (&self.source_ip, &self.source_domain, hostnames) {
+            (Some(ip), domain, hostnames) => match (SiemIp::from_ip_str(ip), hostnames) {
+                (Ok(ip), _) => (ip, domain.as_ref().map(|v| &v[..])),
+                (Err(_), Some(hostnames)) => (resolve_hostname(ip, hostnames), Some(&ip[..])),
+                (Err(_), None) => return Err(true),
+            },
+            (None, Some(domain), Some(hostnames)) => (resolve_hostname(domain, hostnames), Some(&domain[..])),
+            _ => return Err(true),
+        };
+        let squid_status = match &self.squid_status {
+            Some(status) => &status[..],
+            None => "",
+        };
+        let result_code = SquidResultCode::parse(squid_status);
+        let http_code = match &self.http_code {
+            Some(code) => match code.parse::<u32>() {
+                Ok(code) => code,
+                Err(_) => return Err(false),
+            },
+            None => 0,
+        };
+        // With cache peers the server address can be the name of the peer
+        let (destination_ip, peer_host) = match &self.destination_ip {
+            Some(ip) => match SiemIp::from_ip_str(ip) {
+                Ok(ip) => (ip, None),
+                Err(_) => match hostnames {
+                    Some(hostnames) => (resolve_hostname(ip, hostnames), Some(&ip[..])),
+                    None => (SiemIp::V4(0), Some(&ip[..])),
+                },
+            },
+            None => (SiemIp::V4(0), None),
+        };
+        let peer_host = match &self.peer_host {
+            Some(host) => Some(&host[..]),
+            None => peer_host,
+        };
+        let url = match &self.url {
+            Some(url) => match parse_url(url) {
+                Ok(data) => Some(data),
+                Err(_) => return Err(false),
+            },
+            None => None,
+        };
+        let (domain, resource, destination_port) = match &url {
+            Some(url) => (url.domain, url.resource, url.port),
+            None => ("", "", 0),
+        };
+        let domain = match &self.url_domain {
+            Some(v) => &v[..],
+            None => domain,
+        };
+        let protocol = match (&self.url_scheme, &url) {
+            (Some(v), _) => parse_protocol(&v.to_lowercase()),
+            (None, Some(url)) => url.protocol(),
+            (None, None) => WebProtocol::UNKNOWN(String::new()),
+        };
+        let destination_port = match &self.url_port {
+            Some(v) => match v.parse::<u16>() {
+                Ok(v) => v,
+                Err(_) => return Err(false),
+            },
+            None => destination_port,
+        };
+        let in_bytes = match &self.in_bytes {
+            Some(v) => match v.parse::<u64>() {
+                Ok(v) => v,
+                Err(_) => return Err(false),
+            },
+            None => 0,
+        };
+        let out_bytes = match (&self.out_bytes, &self.total_bytes) {
+            (Some(v), _) => match v.parse::<u64>() {
+                Ok(v) => Some(v),
+                Err(_) => return Err(false),
+            },
+            // %st is the request size plus the reply size
+            (None, Some(v)) => match v.parse::<u64>() {
+                Ok(v) => Some(v.saturating_sub(in_bytes)),
+                Err(_) => return Err(false),
+            },
+            (None, None) => None,
+        };
+        let event_created = match (&self.timestamp, &self.date) {
+            (Some(timestamp), _) => {
+                // %tu is a number of milliseconds, not a decimal fraction
+                let timestamp = match (parse_epoch_millis(timestamp), &self.timestamp_millis) {
+                    (Ok(v), Some(millis)) => match millis.parse::<i64>() {
+                        Ok(millis) => Ok(v + millis),
+                        Err(_) => return Err(false),
+                    },
+                    (timestamp, _) => timestamp,
+                };
+                match timestamp {
+                    Ok(v) => Some(v),
+                    Err(_) => return Err(false),
+                }
+            }
+            (None, Some(date)) => match DateTime::parse_from_str(date, "%d/%b/%Y:%H:%M:%S %z") {
+                Ok(date) => Some(date.timestamp_millis()),
+                Err(_) => return Err(false),
+            },
+            (None, None) => syslog_created,
+        };
+        let http_method = match &self.method {
+            Some(method) => HttpMethod::from_str(method),
+            None => HttpMethod::UNKNOWN(String::new()),
+        };
+        let user_name = match &self.user_name {
+            Some(usr) => Cow::Owned(usr.to_string()),
+            None => Cow::Borrowed(""),
+        };
+        let mime_type = match &self.mime_type {
+            Some(mime) => Cow::Owned(mime.to_string()),
+            None => Cow::Borrowed(""),
+        };
+
+        let mut log = SiemLog::new(
+            log_content.to_string(),
+            log.event_received(),
+            log.origin().clone(),
+        );
+        match event_created {
+            Some(event_created) => log.set_event_created(event_created),
+            None => {}
+        };
+        log.set_event(SiemEvent::WebProxy(WebProxyEvent {
+            source_ip,
+            destination_ip,
+            destination_port,
+            domain: Cow::Owned(domain.to_string()),
+            url: Cow::Owned(resource.to_string()),
+            http_method,
+            http_code,
+            mime_type,
+            in_bytes: saturate_bytes(in_bytes),
+            out_bytes: saturate_bytes(out_bytes.unwrap_or(0)),
+            protocol,
+            rule_name: None,
+            rule_category: None,
+            user_name,
+            outcome: match self.squid_status {
+                Some(_) => result_code.outcome(http_code),
+                None => http_outcome(http_code),
+            },
+        }));
+        if self.squid_status.is_some() {
+            result_code.add_fields(&mut log, squid_status);
+        }
+        match &url {
+            Some(url) => add_url_fields(&mut log, url, query_params),
+            None => {}
+        };
+        add_byte_fields(&mut log, in_bytes, out_bytes);
+        match source_domain {
+            Some(domain) => {
+                log.add_field(fields::SOURCE_DOMAIN, SiemField::Text(Cow::Owned(domain.to_lowercase())));
+            }
+            None => {}
+        };
+        match (&self.hierarchy, peer_host) {
+            (Some(hierarchy), _) => add_hierarchy_fields(&mut log, hierarchy, peer_host),
+            (None, Some(host)) => {
+                log.add_field(fields::SQUID_PEER_HOST, SiemField::Text(Cow::Owned(host.to_string())));
+            }
+            (None, None) => {}
+        };
+        match &self.duration {
+            Some(duration) => match duration.parse::<u64>() {
+                Ok(v) => {
+                    log.add_field(field_dictionary::NETWORK_DURATION, SiemField::U64(v));
+                }
+                Err(_) => {}
+            },
+            None => {}
+        };
+        Ok(log)
+    }
+}
+
+/// Compiles a logformat specification into a list of tokens
+pub fn compile(specification: &str) -> Result<Vec<FormatToken>, String> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let chars: Vec<(usize, char)> = specification.char_indices().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let (pos, c) = chars[i];
+        if c != '%' {
+            literal.push(c);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        if i < chars.len() && chars[i].1 == '%' {
+            literal.push('%');
+            i += 1;
+            continue;
+        }
+        let encoding = match chars.get(i).map(|v| v.1) {
+            Some('"') => FieldEncoding::Quoted,
+            Some('[') => FieldEncoding::SquidEscaped,
+            Some('#') => FieldEncoding::UrlEncoded,
+            Some('\'') => FieldEncoding::Raw,
+            _ => FieldEncoding::Default,
+        };
+        if encoding != FieldEncoding::Default {
+            i += 1;
+        }
+        // Alignment and width: %-10.5>a
+        while i < chars.len() && (chars[i].1 == '-' || chars[i].1 == '.' || chars[i].1.is_ascii_digit()) {
+            i += 1;
+        }
+        let mut argument = None;
+        let mut prefix = String::new();
+        if i < chars.len() && (chars[i].1 == '>' || chars[i].1 == '<') && chars.get(i + 1).map(|v| v.1) == Some('{') {
+            // Squid 3 style: %>{Header}h
+            prefix.push(chars[i].1);
+            i += 1;
+        }
+        if i < chars.len() && chars[i].1 == '{' {
+            let start = chars[i].0 + 1;
+            let end = match specification[start..].find('}') {
+                Some(end) => start + end,
+                None => return Err(format!("Unterminated argument at position {}", pos)),
+            };
+            argument = Some(specification[start..end].to_string());
+            while i < chars.len() && chars[i].0 <= end {
+                i += 1;
+            }
+        }
+        let code_start = match chars.get(i) {
+            Some((p, _)) => *p,
+            None => return Err(format!("Missing format code at position {}", pos)),
+        };
+        let remaining = format!("{}{}", prefix, &specification[code_start..]);
+        let (code_text, code) = match FORMAT_CODES.iter().find(|(text, _)| remaining.starts_with(text)) {
+            Some((text, code)) => (text, code.clone()),
+            None => return Err(format!("Unknown format code at position {}", pos)),
+        };
+        i += code_text.chars().count() - prefix.chars().count();
+        if !literal.is_empty() {
+            tokens.push(FormatToken::Literal(literal));
+            literal = String::new();
+        }
+        tokens.push(FormatToken::Field(FormatField {
+            code,
+            argument,
+            encoding,
+        }));
+    }
+    if !literal.is_empty() {
+        tokens.push(FormatToken::Literal(literal));
+    }
+    Ok(tokens)
+}
+
+/// Position of the first `"` not escaped with a backslash
+fn quoted_end(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (pos, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            return Some(pos);
+        }
+    }
+    None
+}
+
+/// Removes backslash escapes: `\"`, `\\`, `\n`, `\t` and `\r`
+pub fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('\\') {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    Cow::Owned(result)
+}
+
+/// Decodes `%XX` sequences
+pub fn url_decode(text: &str) -> Cow<'_, str> {
+    if !text.contains('%') {
+        return Cow::Borrowed(text);
+    }
+    let bytes = text.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            match (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                (Some(high), Some(low)) => {
+                    result.push(high * 16 + low);
+                    i += 3;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        result.push(bytes[i]);
+        i += 1;
+    }
+    Cow::Owned(String::from_utf8_lossy(&result).into_owned())
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FieldEncoding, FormatCode, FormatToken, LogFormat};
+    use super::super::HostTable;
+    use usiem::components::common::{LogParser, LogParsingError};
+    use usiem::events::field::{SiemField, SiemIp};
+    use usiem::events::field_dictionary;
+    use usiem::events::SiemLog;
+    use crate::fields;
+    use crate::timezone::LogTimezone;
+
+    #[test]
+    fn test_compile_format() {
+        let format = LogFormat::from_directive(r#"logformat custom %>a %"{User-Agent}>h %<{Content-Type}h [%tl] %%"#).expect("Must compile");
+        assert_eq!(format.name(), "custom");
+        let tokens = format.tokens();
+        assert_eq!(tokens.len(), 8);
+        match &tokens[2] {
+            FormatToken::Field(field) => {
+                assert_eq!(field.code, FormatCode::RequestHeader);
+                assert_eq!(field.argument, Some(String::from("User-Agent")));
+                assert_eq!(field.encoding, FieldEncoding::Quoted);
+            }
+            _ => panic!("Must be a field"),
+        }
+        match &tokens[4] {
+            FormatToken::Field(field) => {
+                assert_eq!(field.code, FormatCode::ReplyHeader);
+                assert_eq!(field.argument, Some(String::from("Content-Type")));
+            }
+            _ => panic!("Must be a field"),
+        }
+        assert_eq!(tokens[7], FormatToken::Literal(String::from("] %")));
+        assert!(LogFormat::new("bad", "%>a %zz").is_err());
+        // Adapted headers are not the original header followed by an "a"
+        let format = LogFormat::new("adapted", "%{X-Forwarded-For}>ha %<ha").expect("Must compile");
+        let codes: Vec<FormatCode> = format
+            .tokens()
+            .iter()
+            .filter_map(|token| match token {
+                FormatToken::Field(field) => Some(field.code.clone()),
+                FormatToken::Literal(_) => None,
+            })
+            .collect();
+        assert_eq!(codes, vec![FormatCode::AdaptedRequestHeader, FormatCode::AdaptedReplyHeader]);
+        assert_eq!(format.tokens()[1], FormatToken::Literal(String::from(" ")));
+    }
+
+    #[test]
+    fn test_native_format() {
+        let format = LogFormat::squid();
+        let log = "1613260836.628    287 172.17.0.1 TCP_TUNNEL_ABORTED/200 18353 CONNECT www.google.com:443 - HIER_DIRECT/142.250.184.4 -";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        assert!(format.device_match(&log));
+        match format.parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::SOURCE_IP), Some(&SiemField::IP(SiemIp::from_ip_str("172.17.0.1").expect("Must work"))));
+                assert_eq!(log.field(field_dictionary::DESTINATION_IP), Some(&SiemField::IP(SiemIp::from_ip_str("142.250.184.4").expect("Must work"))));
+                assert_eq!(log.field(field_dictionary::HTTP_RESPONSE_STATUS_CODE), Some(&SiemField::U64(200)));
+                assert_eq!(log.field(field_dictionary::URL_DOMAIN), Some(&SiemField::from_str("www.google.com")));
+                assert_eq!(log.field(field_dictionary::DESTINATION_PORT), Some(&SiemField::U64(443)));
+                assert_eq!(log.field(field_dictionary::DESTINATION_BYTES), Some(&SiemField::U64(18353)));
+                assert_eq!(log.field(field_dictionary::NETWORK_DURATION), Some(&SiemField::U64(287)));
+                assert_eq!(log.field(fields::SQUID_HIERARCHY_CODE), Some(&SiemField::from_str("HIER_DIRECT")));
+                assert_eq!(log.event_created(), 1613260836628);
+            }
+            Err(_) => panic!("Cannot parse log"),
+        }
+    }
+
+    #[test]
+    fn test_combined_format() {
+        let format = LogFormat::combined();
+        let log = r#"10.0.0.5 - alice [14/Feb/2021:00:00:36 +0000] "GET http://example.com/index.html HTTP/1.1" 200 5120 "http://referer.com/" "Mozilla/5.0" TCP_MISS:HIER_DIRECT"#;
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match format.parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::EVENT_OUTCOME), Some(&SiemField::from_str("ALLOW")));
+                assert_eq!(log.field(field_dictionary::HTTP_RESPONSE_STATUS_CODE), Some(&SiemField::U64(200)));
+            }
+            Err(_) => panic!("Cannot parse log"),
+        }
+        // Without the Squid result code the outcome comes from the HTTP status
+        let format = LogFormat::new("nostatus", "%ts.%03tu %>a %>Hs %rm %ru").expect("Must compile");
+        let allowed = SiemLog::new("1613260836.628 10.0.0.5 200 GET http://example.com/".to_string(), 0, SiemIp::V4(0));
+        let denied = SiemLog::new("1613260836.628 10.0.0.5 403 GET http://example.com/".to_string(), 0, SiemIp::V4(0));
+        assert_eq!(format.parse_log(allowed).expect("Must work").field(field_dictionary::EVENT_OUTCOME), Some(&SiemField::from_str("ALLOW")));
+        assert_eq!(format.parse_log(denied).expect("Must work").field(field_dictionary::EVENT_OUTCOME), Some(&SiemField::from_str("BLOCK")));
+    }
+
+    #[test]
+    fn test_syslog_date() {
+        // Without time codes the date comes from the syslog header. Received on 2021-02-14 00:10:00 UTC
+        let format = LogFormat::new("notime", "%>a %Ss/%03>Hs %rm %ru").expect("Must compile").with_timezone("+01:00".parse::<LogTimezone>().expect("Must work"));
+        let log = SiemLog::new("<13>Feb 14 01:02:33 proxy squid[26]: 10.0.0.5 TCP_MISS/200 GET http://example.com/".to_string(), 1613261400000, SiemIp::V4(0));
+        assert_eq!(format.parse_log(log).expect("Must work").event_created(), 1613260953000);
+        // The time codes have precedence
+        let format = LogFormat::squid().with_timezone("+01:00".parse::<LogTimezone>().expect("Must work"));
+        let log = SiemLog::new("<13>Feb 14 01:02:33 proxy squid[26]: 1613260836.628    287 172.17.0.1 TCP_TUNNEL/200 18353 CONNECT www.google.com:443 - HIER_DIRECT/142.250.184.4 -".to_string(), 1613261400000, SiemIp::V4(0));
+        assert_eq!(format.parse_log(log).expect("Must work").event_created(), 1613260836628);
+    }
+
+    #[test]
+    fn test_custom_format() {
+        let format = LogFormat::new("custom", r#"%ts.%03tu %>a %un %>Hs %<st %rm %ru %"{User-Agent}>h %"{Referer}>h %Ss"#).expect("Must compile");
+        let log = r#"1613260836.628 10.0.0.5 alice 200 5120 GET http://example.com/index.html?a=1 "Mozilla/5.0 (X11; \"Linux\")" "http://referer.com/" TCP_MISS"#;
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match format.parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::SOURCE_IP), Some(&SiemField::IP(SiemIp::from_ip_str("10.0.0.5").expect("Must work"))));
+                assert_eq!(log.field(field_dictionary::URL_DOMAIN), Some(&SiemField::from_str("example.com")));
+                assert_eq!(log.field(field_dictionary::HTTP_REQUEST_METHOD), Some(&SiemField::from_str("GET")));
+                assert_eq!(log.field(field_dictionary::DESTINATION_BYTES), Some(&SiemField::U64(5120)));
+                assert_eq!(log.field(field_dictionary::EVENT_OUTCOME), Some(&SiemField::from_str("ALLOW")));
+                assert_eq!(log.field(fields::USER_AGENT_ORIGINAL), Some(&SiemField::from_str(r#"Mozilla/5.0 (X11; "Linux")"#)));
+                assert_eq!(log.field(fields::HTTP_REQUEST_REFERRER), Some(&SiemField::from_str("http://referer.com/")));
+                assert_eq!(log.field(field_dictionary::URL_PATH), Some(&SiemField::from_str("/index.html")));
+                assert_eq!(log.field(field_dictionary::URL_QUERY), Some(&SiemField::from_str("?a=1")));
+            }
+            Err(_) => panic!("Cannot parse log"),
+        }
+    }
+
+    #[test]
+    fn test_client_fqdn() {
+        let mut hostnames = HostTable::new();
+        hostnames.insert(String::from("pc-42.corp.local"), SiemIp::from_ip_str("10.0.0.42").expect("Must work"));
+        let format = LogFormat::new("fqdn", "%ts.%03tu %6tr %>A %Ss/%03>Hs %<st %rm %ru %[un %Sh/%<a %mt").expect("Must compile");
+        let log = "1613260836.628    287 PC-42.corp.local TCP_MISS/200 1520 GET http://example.com/ - HIER_DIRECT/93.184.216.34 text/html";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        let log = match format.parse_log(log) {
+            Err(LogParsingError::NoValidParser(log)) => log,
+            _ => panic!("Hostnames must not be accepted by default"),
+        };
+        let format = format.with_fqdn(hostnames);
+        match format.parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::SOURCE_IP), Some(&SiemField::IP(SiemIp::from_ip_str("10.0.0.42").expect("Must work"))));
+                assert_eq!(log.field(fields::SOURCE_DOMAIN), Some(&SiemField::from_str("pc-42.corp.local")));
+            }
+            Err(_) => panic!("Cannot parse log"),
+        }
+    }
+
+    #[test]
+    fn test_request_and_reply_size() {
+        let format = LogFormat::new("sizes", "%ts.%03tu %>a %Ss/%03>Hs %>st %<st %rm %ru").expect("Must compile");
+        let log = "1613260836.628 10.0.0.5 TCP_MISS/200 5368709120 1024 POST http://upload.example.com/files";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match format.parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::SOURCE_BYTES), Some(&SiemField::U64(5368709120)));
+                assert_eq!(log.field(field_dictionary::DESTINATION_BYTES), Some(&SiemField::U64(1024)));
+            }
+            Err(_) => panic!("Cannot parse log"),
+        }
+        let format = LogFormat::new("total", "%ts.%03tu %>a %Ss/%03>Hs %st %<st %rm %ru").expect("Must compile");
+        let log = "1613260836.628 10.0.0.5 TCP_MISS/200 3072 1024 POST http://upload.example.com/files";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match format.parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::SOURCE_BYTES), Some(&SiemField::U64(2048)));
+            }
+            Err(_) => panic!("Cannot parse log"),
+        }
+        let format = LogFormat::new("reply", "%ts.%03tu %>a %Ss/%03>Hs %<st %rm %ru").expect("Must compile");
+        let log = "1613260836.628 10.0.0.5 TCP_MISS/200 1024 GET http://www.example.com/";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match format.parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::DESTINATION_BYTES), Some(&SiemField::U64(1024)));
+                assert_eq!(log.field(field_dictionary::SOURCE_BYTES), Some(&SiemField::U32(0)));
+            }
+            Err(_) => panic!("Cannot parse log"),
+        }
+    }
+}
@@ -1,50 +1,114 @@
 use std::borrow::Cow;
-use usiem::components::common::LogParsingError;
+use usiem::components::common::{LogParser, LogParsingError};
 use usiem::events::common::{HttpMethod, WebProtocol};
 use usiem::events::field::{SiemField, SiemIp};
 use usiem::events::field_dictionary;
 use usiem::events::webproxy::{WebProxyEvent, WebProxyOutcome, WebProxyRuleCategory};
 use usiem::events::{SiemEvent, SiemLog};
-use chrono::{TimeZone, Utc};
-
-pub fn parse_log(log: SiemLog) -> Result<SiemLog, LogParsingError> {
-    let log_line = log.message();
-
-    let request_pos = match log_line.find(" Request(") {
-        Some(pos) => pos,
-        None => return Err(LogParsingError::NoValidParser(log)),
-    };
-
-    let mut log_parsed = Vec::with_capacity(16);
-    let mut last_pos = 0;
+use super::fields;
+use super::squid::logformat::url_decode;
+use super::syslog::{split_syslog, syslog_timestamp};
+use super::timezone::LogTimezone;
+use categories::CategoryMapping;
+use service::SquidGuardServiceEvent;
+
+pub mod categories;
+pub mod config;
+pub mod profiles;
+pub mod service;
+pub mod simulator;
+
+/// SquidGuard log parser, usable as a uSIEM LogParser component.
+#[derive(Clone)]
+pub struct SquidGuardParser {
+    timezone: LogTimezone,
+    categories: CategoryMapping,
+}
 
-    let log_header = &log_line[..request_pos];
+impl Default for SquidGuardParser {
+    fn default() -> SquidGuardParser {
+        SquidGuardParser::new()
+    }
+}
 
-    for (pos, c) in log_header.char_indices() {
-        if c == ' ' {
-            if (last_pos + 1) <= pos {
-                log_parsed.push(&log_header[last_pos..pos]);
-            }
-            last_pos = pos + 1;
+impl SquidGuardParser {
+    pub fn new() -> SquidGuardParser {
+        SquidGuardParser {
+            timezone: LogTimezone::Utc,
+            categories: CategoryMapping::new(),
         }
     }
-    log_parsed.push(&log_header[last_pos..]);
-    
-    if log_parsed.len() < 3 {
-        return Err(LogParsingError::ParserError(log));
+    /// squidGuard writes the local time of the proxy without offset. Defaults to UTC.
+    pub fn with_timezone(mut self, timezone: LogTimezone) -> SquidGuardParser {
+        self.timezone = timezone;
+        self
     }
-    let (date, hours) = match (log_parsed.get(log_parsed.len() - 3),log_parsed.get(log_parsed.len() - 2)) {
-        (Some(date),Some(hour)) => (date,hour),
-        _ => return Err(LogParsingError::ParserError(log))
-    };
-    let event_created = match Utc.datetime_from_str(&format!("{} {}",date,hours)[..], "%Y-%m-%d %H:%M:%S") {
-        Ok(timestamp) => timestamp.timestamp_millis(),
-        Err(_err) => return Err(LogParsingError::ParserError(log)),
+    /// Categories of the destination groups named differently than the built-in defaults
+    pub fn with_categories(mut self, categories: CategoryMapping) -> SquidGuardParser {
+        self.categories = categories;
+        self
+    }
+}
+
+impl LogParser for SquidGuardParser {
+    fn parse_log(&self, log: SiemLog) -> Result<SiemLog, LogParsingError> {
+        parse_log_internal(log, &self.timezone, &self.categories)
+    }
+    fn device_match(&self, log: &SiemLog) -> bool {
+        split_header(log.message()).is_some() || split_syslog(log.message(), "squidGuard").is_some()
+    }
+    fn name(&self) -> &str {
+        "SquidGuard"
+    }
+    fn description(&self) -> &str {
+        "SquidGuard URL filter log parser"
+    }
+}
+
+/// Parses a squidGuard log reading the dates as UTC
+pub fn parse_log(log: SiemLog) -> Result<SiemLog, LogParsingError> {
+    parse_log_with_timezone(log, &LogTimezone::Utc)
+}
+
+/// Parses a squidGuard log written in the local time of the timezone
+pub fn parse_log_with_timezone(log: SiemLog, timezone: &LogTimezone) -> Result<SiemLog, LogParsingError> {
+    parse_log_internal(log, timezone, &CategoryMapping::new())
+}
+
+fn parse_log_internal(log: SiemLog, timezone: &LogTimezone, categories: &CategoryMapping) -> Result<SiemLog, LogParsingError> {
+    let log_line = log.message();
+
+    let (event_created, pid, message) = match split_header(log_line) {
+        Some((date, pid, message)) => match timezone.parse_millis(date, "%Y-%m-%d %H:%M:%S") {
+            Some(timestamp) => (timestamp, pid.parse::<u64>().ok(), message),
+            None => return Err(LogParsingError::ParserError(log)),
+        },
+        // With "syslog enable" squidGuard sends the lines to syslog without its own date
+        None => match (split_syslog(log_line, "squidGuard"), syslog_timestamp(log_line, timezone, log.event_received())) {
+            (Some((pid, message)), Some(timestamp)) => (timestamp, pid, message),
+            (Some(_), None) => return Err(LogParsingError::ParserError(log)),
+            (None, _) => return Err(LogParsingError::NoValidParser(log)),
+        },
     };
+    if !message.starts_with("Request(") {
+        let event = SquidGuardServiceEvent::from_message(message);
+        let pid = pid.unwrap_or(0);
+        let message = message.to_string();
+        let mut log = SiemLog::new(
+            log_line.to_string(),
+            log.event_received(),
+            log.origin().clone(),
+        );
+        log.set_event_created(event_created);
+        log.set_event(SiemEvent::Endpoint);
+        log.add_field(fields::PROCESS_PID, SiemField::U64(pid));
+        event.add_fields(&mut log, &message);
+        return Ok(log);
+    }
 
     let mut log_parsed = Vec::with_capacity(16);
 
-    let log_body = &log_line[request_pos + 1..];
+    let log_body = message;
     let mut last_pos = 0;
     for (pos, c) in log_body.char_indices() {
         if c == ' ' {
@@ -56,15 +120,21 @@ pub fn parse_log(log: SiemLog) -> Result<SiemLog, LogParsingError> {
     }
     log_parsed.push(&log_body[last_pos..]);
 
-    let source_ip = match log_parsed.get(2) {
+    let (source_ip, source_fqdn) = match log_parsed.get(2) {
         Some(source_ip) => {
             match source_ip.find("/") {
-                Some(pos) => &source_ip[..pos],
+                Some(pos) => (&source_ip[..pos], &source_ip[pos + 1..]),
                 None => return Err(LogParsingError::ParserError(log))
             }
         },
         None => return Err(LogParsingError::ParserError(log))
     };
+    // The reverse lookup gives "-" or the IP itself when there is no hostname
+    let source_fqdn = if source_fqdn == "-" || source_fqdn.is_empty() || source_fqdn == source_ip {
+        None
+    } else {
+        Some(source_fqdn.to_lowercase())
+    };
 
     let source_ip = match SiemIp::from_ip_str(source_ip) {
         Ok(ip) => ip,
@@ -88,21 +158,26 @@ pub fn parse_log(log: SiemLog) -> Result<SiemLog, LogParsingError> {
         None => (Some(url.to_string()),None)
     };
 
-    let user_name = match log_parsed.get(3) {
-        Some(user) => {
-            match *user {
-                "-" => Cow::Borrowed(""),
-                usr => Cow::Owned(usr.to_string())
-            }
-        },
-        None => return Err(LogParsingError::ParserError(log))
+    // The user column can have spaces, so the method is searched from the end of the line
+    let method_pos = match (4..log_parsed.len()).rev().find(|pos| is_http_method(log_parsed[*pos])) {
+        Some(pos) => pos,
+        None => 4,
+    };
+    let (user_domain, user_name) = match log_parsed.get(3..method_pos) {
+        Some(user) if !user.is_empty() => parse_user_name(&user.join(" ")),
+        _ => return Err(LogParsingError::ParserError(log))
     };
 
-    let http_method = match log_parsed.get(4) {
+    let http_method = match log_parsed.get(method_pos) {
         Some(method) => HttpMethod::from_str(method),
         None => return Err(LogParsingError::ParserError(log))
     };
 
+    // squidGuard 1.2 does not write the action column and only logs redirections
+    let action_text = log_parsed.get(method_pos + 1).map(|v| *v).unwrap_or("REDIRECT");
+    let action = SquidGuardAction::from_str(action_text, log_parsed.get(method_pos + 2).map(|v| *v));
+    let http_code = action.http_code(&http_method);
+
     let mut log = SiemLog::new(
         log_line.to_string(),
         log.event_received(),
@@ -110,17 +185,17 @@ pub fn parse_log(log: SiemLog) -> Result<SiemLog, LogParsingError> {
     );
     
 
-    let (_rule_ruleset, rule_name) = match log_parsed.get(0) {
+    let (source_group, rule_name, rule_tag) = match log_parsed.get(0) {
         Some(cat) => {
             match parse_rule(cat) {
-                Ok((v1,v2)) => (v1,v2),
+                Ok((v1,v2,v3)) => (v1,v2,v3),
                 Err(_) => return Err(LogParsingError::ParserError(log))
             }
         },
         None => return Err(LogParsingError::ParserError(log))
     };
 
-    let rule_category = rule_category(rule_name);
+    let rule_category = categories.category(rule_name);
 
 
     log.set_event_created(event_created);
@@ -131,16 +206,31 @@ pub fn parse_log(log: SiemLog) -> Result<SiemLog, LogParsingError> {
         domain: Cow::Owned(domain.to_string()),
         url: Cow::Owned(url.to_string()),
         http_method,
-        http_code : 503,
+        http_code,
         mime_type : Cow::Borrowed(""),
         in_bytes : 0,
         out_bytes: 0,
         protocol: parse_protocol(protocol),
         rule_name: Some(Cow::Owned(rule_name.to_string())),
         rule_category: Some(rule_category),
-        user_name,
-        outcome: WebProxyOutcome::BLOCK,
+        user_name: Cow::Owned(user_name),
+        outcome: action.outcome(),
     }));
+    if let Some(source_fqdn) = source_fqdn {
+        log.add_field(fields::SOURCE_DOMAIN, SiemField::Text(Cow::Owned(source_fqdn)));
+    }
+    if let Some(user_domain) = user_domain {
+        log.add_field(field_dictionary::USER_DOMAIN, SiemField::Text(Cow::Owned(user_domain)));
+    }
+    log.add_field(fields::SQUIDGUARD_SOURCE_GROUP, SiemField::Text(Cow::Owned(source_group.to_string())));
+    log.add_field(fields::SQUIDGUARD_DESTINATION_GROUP, SiemField::Text(Cow::Owned(rule_name.to_string())));
+    if rule_tag != "-" && !rule_tag.is_empty() {
+        log.add_field(fields::SQUIDGUARD_TAG, SiemField::Text(Cow::Owned(rule_tag.to_string())));
+    }
+    log.add_field(fields::SQUIDGUARD_ACTION, SiemField::Text(Cow::Owned(action_text.to_string())));
+    if let SquidGuardAction::Rewrite(Some(target)) = &action {
+        log.add_field(fields::SQUIDGUARD_REWRITE_TARGET, SiemField::Text(Cow::Owned(target.to_string())));
+    }
     match log_parsed[1].parse::<u64>() {
         Ok(v) => {
             log.add_field(field_dictionary::NETWORK_DURATION, SiemField::U64(v));
@@ -163,82 +253,120 @@ pub fn parse_log(log: SiemLog) -> Result<SiemLog, LogParsingError> {
     return Ok(log);
 }
 
-/// Categories based on http://www.shallalist.de/categories.html
-pub fn rule_category(text: &str) -> WebProxyRuleCategory {
-    if text.contains("automobile"){
-        return WebProxyRuleCategory::Vehicles
-    }else if text.contains("finance"){
-        return WebProxyRuleCategory::Finance
-    }else if text.contains("hobby"){
-        return WebProxyRuleCategory::PersonalSites
+/// URL-decodes the user column and splits Windows `DOMAIN\\user` names. Returns the domain and the user name.
+/// The spaces around the names are trimmed. The case is kept as logged: Squid `proxy_auth` names are case-sensitive.
+pub fn parse_user_name(text: &str) -> (Option<String>, String) {
+    let text = text.trim();
+    if text == "-" || text.is_empty() {
+        return (None, String::new());
     }
-    
+    let user = url_decode(text);
+    let (domain, user) = match user.find('\\') {
+        Some(pos) => (user[..pos].trim(), user[pos + 1..].trim()),
+        None => ("", user.trim()),
+    };
+    let domain = if domain.is_empty() { None } else { Some(domain.to_string()) };
+    (domain, user.to_string())
+}
+
+fn is_http_method(text: &str) -> bool {
     match text {
-        "adv" => WebProxyRuleCategory::Spam,
-        "aggressive" => WebProxyRuleCategory::QuestionableLegality,
-        "alcohol" => WebProxyRuleCategory::Alcohol,
-        "anonvpn" => WebProxyRuleCategory::ProxyAvoidance,
-        "chat" => WebProxyRuleCategory::OnlineChat,
-        "costtraps" => WebProxyRuleCategory::Phishing,
-        "dating" => WebProxyRuleCategory::PersonalsDating,
-        "downloads" => WebProxyRuleCategory::P2P,
-        "drugs" => WebProxyRuleCategory::Marijuana,
-        "dynamic" => WebProxyRuleCategory::DynamicDNSHost,
-        "schools" => WebProxyRuleCategory::Education,
-        "education" => WebProxyRuleCategory::Education,
-        "fortunetelling" => WebProxyRuleCategory::AlternativeSpirituality,
-        "forum" => WebProxyRuleCategory::Forums,
-        "gamble" => WebProxyRuleCategory::Gambling,
-        "government" => WebProxyRuleCategory::Government,
-        "hacking" => WebProxyRuleCategory::Hacking,
-        "homestyle" => WebProxyRuleCategory::PersonalSites,
-        "hospitals" => WebProxyRuleCategory::Health,
-        "imagehosting" => WebProxyRuleCategory::WebHosting,
-        "isp" => WebProxyRuleCategory::InternetTelephony,
-        "jobsearch" => WebProxyRuleCategory::JobSearch,
-        "library" => WebProxyRuleCategory::Education,
-        "military" => WebProxyRuleCategory::Military,
-        "movies" => WebProxyRuleCategory::VideoStreams,
-        "music" => WebProxyRuleCategory::AudioVideoClips,
-        "news" => WebProxyRuleCategory::News,
-        "podcasts" => WebProxyRuleCategory::RadioAudioStreams,
-        "politics" => WebProxyRuleCategory::PoliticalAdvocacy,
-        "porn" => WebProxyRuleCategory::Pornography,
-        "radiotv" => WebProxyRuleCategory::RadioAudioStreams,
-        "humor" => WebProxyRuleCategory::HumorJokes,
-        "martialarts" => WebProxyRuleCategory::Sports,
-        "restaurants" => WebProxyRuleCategory::Restaurants,
-        "sports" => WebProxyRuleCategory::Sports,
-        "travel" => WebProxyRuleCategory::Travel,
-        "wellness" => WebProxyRuleCategory::Health,
-        "recreation" => WebProxyRuleCategory::Entertainment,
-        "redirector" => WebProxyRuleCategory::URLShorteners,
-        "religion" => WebProxyRuleCategory::Religion,
-        "remotecontrol" => WebProxyRuleCategory::RemoteAccess,
-        "ringtones" => WebProxyRuleCategory::InternetTelephony,
-        "astronomy" => WebProxyRuleCategory::Education,
-        "science" => WebProxyRuleCategory::Education,
-        "chemistry" => WebProxyRuleCategory::Education,
-        "searchengines" => WebProxyRuleCategory::SearchEngines,
-        "lingerie" => WebProxyRuleCategory::IntimateApparel,
-        "sex" => WebProxyRuleCategory::SexualExpression,
-        "shopping" => WebProxyRuleCategory::Shopping,
-        "socialnet" => WebProxyRuleCategory::SocialNetworking,
-        "spyware" => WebProxyRuleCategory::MaliciousSources,
-        "tracker" => WebProxyRuleCategory::WebAds,
-        "updatesites" => WebProxyRuleCategory::SoftwareDownloads,
-        "urlshortener" => WebProxyRuleCategory::URLShorteners,
-        "violence" => WebProxyRuleCategory::Violence,
-        "warez" => WebProxyRuleCategory::CopyrightConcerns,
-        "weapons" => WebProxyRuleCategory::Weapons,
-        "webmail" => WebProxyRuleCategory::Email,
-        "webphone" => WebProxyRuleCategory::OnlineChat,
-        "webradio" => WebProxyRuleCategory::RadioAudioStreams,
-        "webtv" => WebProxyRuleCategory::VideoStreams,
-        _ => WebProxyRuleCategory::Uncategorized,
+        "GET" | "POST" | "PUT" | "PATCH" | "DELETE" | "HEAD" | "OPTIONS" | "CONNECT" | "TRACE" | "PROPFIND"
+        | "PROPPATCH" | "MKCOL" | "COPY" | "MOVE" | "LOCK" | "UNLOCK" | "SEARCH" | "PURGE" => true,
+        _ => false,
     }
 }
 
+/// Last column of the request lines: what squidGuard did with the request
+#[derive(Debug, PartialEq, Clone)]
+pub enum SquidGuardAction {
+    /// The request matched a blocked destination and was redirected to the block page
+    Redirect,
+    /// The request was allowed. Only logged when the `pass` rule has a `log` statement
+    Pass,
+    /// The URL was rewritten by a rewrite rule. Contains the new URL when it is logged
+    Rewrite(Option<String>),
+    Unknown(String),
+}
+
+impl SquidGuardAction {
+    /// Parses the action column. The next column is the rewrite target for `REWRITE` actions.
+    pub fn from_str(action: &str, next: Option<&str>) -> SquidGuardAction {
+        match action.to_uppercase().as_str() {
+            "REDIRECT" => SquidGuardAction::Redirect,
+            "PASS" => SquidGuardAction::Pass,
+            "REWRITE" => SquidGuardAction::Rewrite(next.map(|v| v.to_string())),
+            _ => {
+                if action.contains("://") {
+                    SquidGuardAction::Rewrite(Some(action.to_string()))
+                } else {
+                    SquidGuardAction::Unknown(action.to_string())
+                }
+            }
+        }
+    }
+
+    pub fn outcome(&self) -> WebProxyOutcome {
+        match self {
+            SquidGuardAction::Pass | SquidGuardAction::Rewrite(_) => WebProxyOutcome::ALLOW,
+            _ => WebProxyOutcome::BLOCK,
+        }
+    }
+
+    /// Status code seen by the user, 0 if squidGuard cannot know it.
+    /// A CONNECT cannot be redirected, so Squid answers the tunnel request with a 503.
+    pub fn http_code(&self, method: &HttpMethod) -> u32 {
+        match (self, method) {
+            (SquidGuardAction::Redirect, HttpMethod::CONNECT) => 503,
+            _ => 0,
+        }
+    }
+}
+
+/// Splits the `YYYY-MM-DD HH:MM:SS [pid] message` squidGuard header, skipping the syslog header if any.
+/// Returns the date, the pid and the message.
+pub fn split_header<'a>(log_line: &'a str) -> Option<(&'a str, &'a str, &'a str)> {
+    for (pos, _) in log_line.match_indices(" [") {
+        if pos < 19 {
+            continue;
+        }
+        let date = match log_line.get(pos - 19..pos) {
+            Some(date) => date,
+            None => continue,
+        };
+        if !has_shape(date, "dddd-dd-dd dd:dd:dd") {
+            continue;
+        }
+        let pid_text = &log_line[pos + 2..];
+        let pid_end = match pid_text.find("]") {
+            Some(end) => end,
+            None => continue,
+        };
+        let pid = &pid_text[..pid_end];
+        if pid.is_empty() || !pid.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        return Some((date, pid, pid_text[pid_end + 1..].trim()));
+    }
+    None
+}
+
+/// Checks a text against a template where `d` stands for any digit
+pub(crate) fn has_shape(text: &str, shape: &str) -> bool {
+    text.len() == shape.len()
+        && text.chars().zip(shape.chars()).all(|(c, s)| match s {
+            'd' => c.is_ascii_digit(),
+            _ => c == s,
+        })
+}
+
+/// Categories based on the Shalla list names: http://www.shallalist.de/categories.html
+/// Hierarchical names like `finance/banking` fall back to the parent if there is no finer category.
+/// Other lists are available as profiles in the `profiles` module.
+pub fn rule_category(text: &str) -> WebProxyRuleCategory {
+    profiles::CategoryProfile::Shalla.category(text)
+}
+
 pub fn parse_outcome(text: &str) -> WebProxyOutcome {
     match text {
         "NONE" => WebProxyOutcome::BLOCK,
@@ -270,7 +398,9 @@ pub fn http_method(method: &str) -> HttpMethod {
     }
 }
 
-pub fn parse_rule<'a>(val: &'a str) -> Result<(&'a str,&'a str), &'static str> {
+/// Splits `Request(source/destination/tag)` into the source group (the acl that matched the client),
+/// the destination group and the extra tag: the rewrite rule or `-`.
+pub fn parse_rule<'a>(val: &'a str) -> Result<(&'a str, &'a str, &'a str), &'static str> {
     let start_pos = match val.find("("){
         Some(pos) => pos,
         None => return Err("")
@@ -285,7 +415,11 @@ pub fn parse_rule<'a>(val: &'a str) -> Result<(&'a str,&'a str), &'static str> {
         None => return Err("")
     };
     let subcat = &val[cat_end + 1..subcat_end];
-    Ok((cat_ruleset, subcat))
+    let tag = match val[subcat_end + 1..].rfind(")") {
+        Some(pos) => &val[subcat_end + 1..subcat_end + 1 + pos],
+        None => &val[subcat_end + 1..],
+    };
+    Ok((cat_ruleset, subcat, tag))
 }
 
 pub fn parse_url<'a>(url: &'a str) -> Result<(&'a str, &'a str, &'a str, u16), &'static str> {
@@ -333,9 +467,11 @@ pub fn destination_ip_from_squid<'a>(text: &'a str) -> Result<(&'a str, &'a str)
 
 #[cfg(test)]
 mod test {
+    use usiem::components::common::LogParser;
     use usiem::events::{SiemLog};
     use usiem::events::field::{SiemIp,SiemField};
     use usiem::events::field_dictionary;
+    use crate::timezone::LogTimezone;
     #[test]
     fn test_log_from_file() {
         let log = "2021-02-14 00:02:33 [26] Request(default/porn/-) pornpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT";
@@ -358,5 +494,206 @@ mod test {
         }
     }
 
-    
-}
\ No newline at end of file
+    #[test]
+    fn test_log_parser_component() {
+        let parser = super::SquidGuardParser::new();
+        let log = "2021-02-14 00:02:33 [26] Request(default/porn/-) pornpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        assert!(parser.device_match(&log));
+        match parser.parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::URL_DOMAIN), Some(&SiemField::from_str("pornpage.com")));
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+    }
+
+    #[test]
+    fn test_log_timezone() {
+        let timezone = "+01:00".parse::<LogTimezone>().expect("Must work");
+        let parser = super::SquidGuardParser::new().with_timezone(timezone);
+        let log = "2021-02-14 01:02:33 [26] Request(default/porn/-) pornpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match parser.parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.event_created(), 1613260953000);
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+        // Sent to syslog without the squidGuard date. Received on 2021-02-14 00:10:00 UTC
+        let log = "<13>Feb 14 01:02:33 proxy squidGuard[26]: Request(default/porn/-) pornpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT";
+        let log = SiemLog::new(log.to_string(), 1613261400000, SiemIp::V4(0));
+        assert!(parser.device_match(&log));
+        match parser.parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.event_created(), 1613260953000);
+                assert_eq!(log.field(field_dictionary::URL_DOMAIN), Some(&SiemField::from_str("pornpage.com")));
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+    }
+
+    #[test]
+    fn test_log_custom_categories() {
+        let categories = super::categories::CategoryMapping::from_text("hack = Hacking").expect("Must work");
+        let parser = super::SquidGuardParser::new().with_categories(categories);
+        let log = "2021-02-14 00:02:33 [26] Request(default/hack/-) hackpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match parser.parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::RULE_CATEGORY), Some(&SiemField::from_str("Hacking")));
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(super::parse_rule("Request(default/porn/-)"), Ok(("default", "porn", "-")));
+        assert_eq!(super::parse_rule("Request(students/none/safesearch)"), Ok(("students", "none", "safesearch")));
+        assert!(super::parse_rule("Request(default)").is_err());
+    }
+
+    #[test]
+    fn test_log_rule_fields() {
+        let log = "2021-02-14 00:02:33 [26] Request(students/porn/-) pornpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match super::parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(crate::fields::SQUIDGUARD_SOURCE_GROUP), Some(&SiemField::from_str("students")));
+                assert_eq!(log.field(crate::fields::SQUIDGUARD_DESTINATION_GROUP), Some(&SiemField::from_str("porn")));
+                assert_eq!(log.field(field_dictionary::RULE_NAME), Some(&SiemField::from_str("porn")));
+                assert_eq!(log.field(crate::fields::SQUIDGUARD_TAG), None);
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_user_name() {
+        assert_eq!(super::parse_user_name("-"), (None, String::new()));
+        assert_eq!(super::parse_user_name("alice"), (None, "alice".to_string()));
+        assert_eq!(super::parse_user_name("CORP%5Calice"), (Some("CORP".to_string()), "alice".to_string()));
+        assert_eq!(super::parse_user_name("CORP\\john smith"), (Some("CORP".to_string()), "john smith".to_string()));
+        assert_eq!(super::parse_user_name("john%20smith"), (None, "john smith".to_string()));
+        assert_eq!(super::parse_user_name(" corp%5CJohn%20Smith%20 "), (Some("corp".to_string()), "John Smith".to_string()));
+        assert_eq!(super::parse_user_name("%5CAlice"), (None, "Alice".to_string()));
+    }
+
+    #[test]
+    fn test_log_user_and_fqdn() {
+        let log = "2021-02-14 00:02:33 [26] Request(default/porn/-) http://pornpage.com/ 10.0.0.5/PC-42.corp.local CORP\\john smith GET REDIRECT";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match super::parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::SOURCE_IP), Some(&SiemField::IP(SiemIp::from_ip_str("10.0.0.5").expect("Must work"))));
+                assert_eq!(log.field(crate::fields::SOURCE_DOMAIN), Some(&SiemField::from_str("pc-42.corp.local")));
+                assert_eq!(log.field(field_dictionary::USER_NAME), Some(&SiemField::User("john smith".to_string())));
+                assert_eq!(log.field(field_dictionary::USER_DOMAIN), Some(&SiemField::from_str("CORP")));
+                assert_eq!(log.field(crate::fields::SQUIDGUARD_ACTION), Some(&SiemField::from_str("REDIRECT")));
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+        let log = "2021-02-14 00:02:33 [26] Request(default/porn/-) http://pornpage.com/ 10.0.0.5/- CORP%5CJohn%20Smith GET REDIRECT";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match super::parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::USER_NAME), Some(&SiemField::User("John Smith".to_string())));
+                assert_eq!(log.field(field_dictionary::USER_DOMAIN), Some(&SiemField::from_str("CORP")));
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+        let log = "2021-02-14 00:02:33 [26] Request(default/porn/-) pornpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match super::parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(crate::fields::SOURCE_DOMAIN), None);
+                assert_eq!(log.field(field_dictionary::USER_DOMAIN), None);
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+    }
+
+    #[test]
+    fn test_log_actions() {
+        let log = "2021-02-14 00:02:33 [26] Request(default/none/-) http://example.com/index.html 172.17.0.1/172.17.0.1 - GET PASS";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match super::parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::EVENT_OUTCOME), Some(&SiemField::from_str("ALLOW")));
+                assert_eq!(log.field(field_dictionary::HTTP_RESPONSE_STATUS_CODE), Some(&SiemField::U64(0)));
+                assert_eq!(log.field(crate::fields::SQUIDGUARD_ACTION), Some(&SiemField::from_str("PASS")));
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+        let log = "2021-02-14 00:02:33 [26] Request(default/none/safesearch) http://www.google.com/search?q=test 172.17.0.1/172.17.0.1 - GET REWRITE http://www.google.com/search?q=test&safe=active";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match super::parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::EVENT_OUTCOME), Some(&SiemField::from_str("ALLOW")));
+                assert_eq!(log.field(crate::fields::SQUIDGUARD_REWRITE_TARGET), Some(&SiemField::from_str("http://www.google.com/search?q=test&safe=active")));
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+        let log = "2021-02-14 00:02:33 [26] Request(default/porn/-) http://pornpage.com/ 172.17.0.1/172.17.0.1 - GET REDIRECT";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match super::parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::EVENT_OUTCOME), Some(&SiemField::from_str("BLOCK")));
+                assert_eq!(log.field(field_dictionary::HTTP_RESPONSE_STATUS_CODE), Some(&SiemField::U64(0)));
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+    }
+
+    #[test]
+    fn test_service_logs() {
+        let log = "<13>Feb 14 00:02:33 proxy squidGuard: 2021-02-14 00:02:33 [26] Going into emergency mode";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match super::parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::EVENT_ACTION), Some(&SiemField::from_str("emergency_mode")));
+                assert_eq!(log.field(crate::fields::PROCESS_PID), Some(&SiemField::U64(26)));
+                assert_eq!(log.event_created(), 1613260953000);
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+        let log = "2021-02-14 00:02:33 [26] init domainlist /var/lib/squidguard/db/porn/domains";
+        let log = SiemLog::new(log.to_string(), 0, SiemIp::V4(0));
+        match super::parse_log(log) {
+            Ok(log) => {
+                assert_eq!(log.field(field_dictionary::EVENT_ACTION), Some(&SiemField::from_str("db_load")));
+                assert_eq!(log.field(crate::fields::FILE_PATH), Some(&SiemField::from_str("/var/lib/squidguard/db/porn/domains")));
+            },
+            Err(_) => {
+                panic!("Cannot parse log")
+            }
+        }
+        let log = SiemLog::new("Feb 14 00:02:33 proxy sshd[22]: Accepted password for root".to_string(), 0, SiemIp::V4(0));
+        assert!(!super::SquidGuardParser::new().device_match(&log));
+    }
+}
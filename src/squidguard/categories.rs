@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use usiem::events::webproxy::WebProxyRuleCategory;
+use super::profiles::CategoryProfile;
+
+/// Category of each squidGuard destination group, layered over the built-in profile of a blacklist vendor.
+///
+/// Can be loaded from TOML or JSON files with a `group = "Category"` map, or from a text file:
+/// ```text
+/// # dest group = category
+/// hack = Hacking
+/// additional = Uncategorized
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CategoryMapping {
+    profile: CategoryProfile,
+    groups: BTreeMap<String, WebProxyRuleCategory>,
+}
+
+impl CategoryMapping {
+    /// Mapping without overrides: only the Shalla profile
+    pub fn new() -> CategoryMapping {
+        CategoryMapping {
+            profile: CategoryProfile::Shalla,
+            groups: BTreeMap::new(),
+        }
+    }
+
+    /// Vendor list used for the groups without override
+    pub fn with_profile(mut self, profile: CategoryProfile) -> CategoryMapping {
+        self.profile = profile;
+        self
+    }
+
+    pub fn profile(&self) -> &CategoryProfile {
+        &self.profile
+    }
+
+    pub fn insert(&mut self, group: &str, category: WebProxyRuleCategory) {
+        self.groups.insert(group.to_lowercase(), category);
+    }
+
+    /// Adds the groups of another mapping, replacing the ones already present. The profile is not changed.
+    pub fn extend(&mut self, other: CategoryMapping) {
+        self.groups.extend(other.groups);
+    }
+
+    /// Category configured for the group, without the defaults
+    pub fn get(&self, group: &str) -> Option<&WebProxyRuleCategory> {
+        self.groups.get(&group.to_lowercase())
+    }
+
+    /// Category of the destination group. For each level of the name, from the group to its
+    /// farthest parent, the configured rules are checked before the profile.
+    pub fn category(&self, group: &str) -> WebProxyRuleCategory {
+        let overrides = self.groups.iter().map(|(name, category)| (name.as_str(), category));
+        let profile = self.profile.groups().iter().map(|(name, category)| (*name, category));
+        let category = resolve_category(group, |level| {
+            match_level(overrides.clone(), level).or_else(|| match_level(profile.clone(), level))
+        });
+        match category {
+            Some(category) => category,
+            None => WebProxyRuleCategory::Uncategorized,
+        }
+    }
+
+    /// Text format: one `group = Category` per line. `#` starts a comment.
+    pub fn from_text(content: &str) -> Result<CategoryMapping, String> {
+        let mut mapping = CategoryMapping::new();
+        for (line_number, line) in content.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(pos) => &line[..pos],
+                None => line,
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (group, category) = match line.find(|c: char| c == '=' || c == ':' || c.is_whitespace()) {
+                Some(pos) => (&line[..pos], line[pos..].trim_start_matches(|c: char| c == '=' || c == ':' || c.is_whitespace())),
+                None => return Err(format!("Line {}: expected 'group = Category'", line_number + 1)),
+            };
+            if group.is_empty() || category.is_empty() {
+                return Err(format!("Line {}: expected 'group = Category'", line_number + 1));
+            }
+            match category_from_name(category) {
+                Some(category) => mapping.insert(group, category),
+                None => return Err(format!("Line {}: unknown category '{}'", line_number + 1, category)),
+            }
+        }
+        Ok(mapping)
+    }
+
+    /// JSON object: `{"hack": "Hacking"}`
+    pub fn from_json(content: &str) -> Result<CategoryMapping, String> {
+        match serde_json::from_str::<BTreeMap<String, String>>(content) {
+            Ok(groups) => CategoryMapping::from_map(groups),
+            Err(err) => Err(format!("Invalid JSON category mapping: {}", err)),
+        }
+    }
+
+    /// TOML table: `hack = "Hacking"`
+    pub fn from_toml(content: &str) -> Result<CategoryMapping, String> {
+        match toml::from_str::<BTreeMap<String, String>>(content) {
+            Ok(groups) => CategoryMapping::from_map(groups),
+            Err(err) => Err(format!("Invalid TOML category mapping: {}", err)),
+        }
+    }
+
+    /// Loads a file choosing the format by extension: `.toml`, `.json` or text for anything else
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<CategoryMapping, String> {
+        let path = path.as_ref();
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) => return Err(format!("Cannot read {}: {}", path.display(), err)),
+        };
+        match path.extension().and_then(|v| v.to_str()) {
+            Some("toml") => CategoryMapping::from_toml(&content),
+            Some("json") => CategoryMapping::from_json(&content),
+            _ => CategoryMapping::from_text(&content),
+        }
+    }
+
+    fn from_map(groups: BTreeMap<String, String>) -> Result<CategoryMapping, String> {
+        let mut mapping = CategoryMapping::new();
+        for (group, category) in groups {
+            match category_from_name(&category) {
+                Some(category) => mapping.insert(&group, category),
+                None => return Err(format!("Unknown category '{}' for group '{}'", category, group)),
+            }
+        }
+        Ok(mapping)
+    }
+}
+
+/// Resolves hierarchical group names like `hobby/games-online`. The name is checked first, then each parent
+/// from the nearest to the farthest (`hobby`), and the first level with a matching rule gives the category.
+pub fn resolve_category<F>(group: &str, match_rules: F) -> Option<WebProxyRuleCategory>
+where
+    F: Fn(&str) -> Option<WebProxyRuleCategory>,
+{
+    let group = group.trim_matches('/').to_lowercase();
+    let mut level = group.as_str();
+    loop {
+        if let Some(category) = match_rules(level) {
+            return Some(category);
+        }
+        match level.rfind('/') {
+            Some(pos) => level = &level[..pos],
+            None => return None,
+        }
+    }
+}
+
+/// Finds the rule for one level of the name. An exact name wins over the patterns with `*`
+/// (`finance*` as prefix or `hobby/games-*` as wildcard), and between patterns the longest one wins.
+pub fn match_level<'a, I>(rules: I, level: &str) -> Option<WebProxyRuleCategory>
+where
+    I: Iterator<Item = (&'a str, &'a WebProxyRuleCategory)>,
+{
+    let mut best: Option<(usize, &WebProxyRuleCategory)> = None;
+    for (rule, category) in rules {
+        if !rule.contains('*') {
+            if rule == level {
+                return Some(category.clone());
+            }
+            continue;
+        }
+        let specificity = rule.len() - rule.matches('*').count();
+        if best.map(|(v, _)| specificity > v).unwrap_or(true) && wildcard_match(rule, level) {
+            best = Some((specificity, category));
+        }
+    }
+    best.map(|(_, category)| category.clone())
+}
+
+/// Matches a text against a pattern where `*` stands for any sequence of characters
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !text.starts_with(first) {
+        return false;
+    }
+    let mut remaining = &text[first.len()..];
+    let parts: Vec<&str> = parts.collect();
+    if parts.is_empty() {
+        return remaining.is_empty();
+    }
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return remaining.ends_with(part);
+        }
+        match remaining.find(part) {
+            Some(pos) => remaining = &remaining[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Inverse of the `WebProxyRuleCategory` Display, ignoring case
+pub fn category_from_name(name: &str) -> Option<WebProxyRuleCategory> {
+    let category = match name.to_lowercase().as_str() {
+        "abortion" => WebProxyRuleCategory::Abortion,
+        "maturecontent" => WebProxyRuleCategory::MatureContent,
+        "alcohol" => WebProxyRuleCategory::Alcohol,
+        "alternativespirituality" => WebProxyRuleCategory::AlternativeSpirituality,
+        "artculture" => WebProxyRuleCategory::ArtCulture,
+        "auctions" => WebProxyRuleCategory::Auctions,
+        "audiovideoclips" => WebProxyRuleCategory::AudioVideoClips,
+        "trading" => WebProxyRuleCategory::Trading,
+        "economy" => WebProxyRuleCategory::Economy,
+        "charitable" => WebProxyRuleCategory::Charitable,
+        "onlinechat" => WebProxyRuleCategory::OnlineChat,
+        "childpornography" => WebProxyRuleCategory::ChildPornography,
+        "cloudinfrastructure" => WebProxyRuleCategory::CloudInfrastructure,
+        "compromisedsites" => WebProxyRuleCategory::CompromisedSites,
+        "informationsecurity" => WebProxyRuleCategory::InformationSecurity,
+        "contentdeliverynetworks" => WebProxyRuleCategory::ContentDeliveryNetworks,
+        "controlledsubstances" => WebProxyRuleCategory::ControlledSubstances,
+        "cryptocurrency" => WebProxyRuleCategory::Cryptocurrency,
+        "dynamicdnshost" => WebProxyRuleCategory::DynamicDNSHost,
+        "ecardinvitations" => WebProxyRuleCategory::ECardInvitations,
+        "education" => WebProxyRuleCategory::Education,
+        "email" => WebProxyRuleCategory::Email,
+        "emailmarketing" => WebProxyRuleCategory::EmailMarketing,
+        "entertainment" => WebProxyRuleCategory::Entertainment,
+        "filestorage" => WebProxyRuleCategory::FileStorage,
+        "finance" => WebProxyRuleCategory::Finance,
+        "forkids" => WebProxyRuleCategory::ForKids,
+        "gambling" => WebProxyRuleCategory::Gambling,
+        "games" => WebProxyRuleCategory::Games,
+        "gore" => WebProxyRuleCategory::Gore,
+        "government" => WebProxyRuleCategory::Government,
+        "hacking" => WebProxyRuleCategory::Hacking,
+        "health" => WebProxyRuleCategory::Health,
+        "humorjokes" => WebProxyRuleCategory::HumorJokes,
+        "informational" => WebProxyRuleCategory::Informational,
+        "internetconnecteddevices" => WebProxyRuleCategory::InternetConnectedDevices,
+        "internettelephony" => WebProxyRuleCategory::InternetTelephony,
+        "intimateapparel" => WebProxyRuleCategory::IntimateApparel,
+        "jobsearch" => WebProxyRuleCategory::JobSearch,
+        "maliciousoutbounddatabotnets" => WebProxyRuleCategory::MaliciousOutboundDataBotnets,
+        "malicioussources" => WebProxyRuleCategory::MaliciousSources,
+        "marijuana" => WebProxyRuleCategory::Marijuana,
+        "mediasharing" => WebProxyRuleCategory::MediaSharing,
+        "military" => WebProxyRuleCategory::Military,
+        "potentiallyadult" => WebProxyRuleCategory::PotentiallyAdult,
+        "news" => WebProxyRuleCategory::News,
+        "forums" => WebProxyRuleCategory::Forums,
+        "nudity" => WebProxyRuleCategory::Nudity,
+        "businessapplications" => WebProxyRuleCategory::BusinessApplications,
+        "onlinemeetings" => WebProxyRuleCategory::OnlineMeetings,
+        "p2p" => WebProxyRuleCategory::P2P,
+        "personalsites" => WebProxyRuleCategory::PersonalSites,
+        "personalsdating" => WebProxyRuleCategory::PersonalsDating,
+        "phishing" => WebProxyRuleCategory::Phishing,
+        "copyrightconcerns" => WebProxyRuleCategory::CopyrightConcerns,
+        "placeholders" => WebProxyRuleCategory::Placeholders,
+        "politicaladvocacy" => WebProxyRuleCategory::PoliticalAdvocacy,
+        "pornography" => WebProxyRuleCategory::Pornography,
+        "potentiallyunwantedsoftware" => WebProxyRuleCategory::PotentiallyUnwantedSoftware,
+        "proxyavoidance" => WebProxyRuleCategory::ProxyAvoidance,
+        "radioaudiostreams" => WebProxyRuleCategory::RadioAudioStreams,
+        "realestate" => WebProxyRuleCategory::RealEstate,
+        "reference" => WebProxyRuleCategory::Reference,
+        "religion" => WebProxyRuleCategory::Religion,
+        "remoteaccess" => WebProxyRuleCategory::RemoteAccess,
+        "restaurants" => WebProxyRuleCategory::Restaurants,
+        "questionablelegality" => WebProxyRuleCategory::QuestionableLegality,
+        "searchengines" => WebProxyRuleCategory::SearchEngines,
+        "sexeducation" => WebProxyRuleCategory::SexEducation,
+        "sexualexpression" => WebProxyRuleCategory::SexualExpression,
+        "shopping" => WebProxyRuleCategory::Shopping,
+        "socialnetworking" => WebProxyRuleCategory::SocialNetworking,
+        "dailyliving" => WebProxyRuleCategory::DailyLiving,
+        "softwaredownloads" => WebProxyRuleCategory::SoftwareDownloads,
+        "spam" => WebProxyRuleCategory::Spam,
+        "sports" => WebProxyRuleCategory::Sports,
+        "suspicious" => WebProxyRuleCategory::Suspicious,
+        "technology" => WebProxyRuleCategory::Technology,
+        "tobacco" => WebProxyRuleCategory::Tobacco,
+        "translation" => WebProxyRuleCategory::Translation,
+        "travel" => WebProxyRuleCategory::Travel,
+        "videostreams" => WebProxyRuleCategory::VideoStreams,
+        "uncategorized" => WebProxyRuleCategory::Uncategorized,
+        "urlshorteners" => WebProxyRuleCategory::URLShorteners,
+        "vehicles" => WebProxyRuleCategory::Vehicles,
+        "violence" => WebProxyRuleCategory::Violence,
+        "weapons" => WebProxyRuleCategory::Weapons,
+        "webads" => WebProxyRuleCategory::WebAds,
+        "webhosting" => WebProxyRuleCategory::WebHosting,
+        "webinfrastructure" => WebProxyRuleCategory::WebInfrastructure,
+        _ => return None,
+    };
+    Some(category)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{category_from_name, wildcard_match, CategoryMapping};
+    use crate::squidguard::profiles::CategoryProfile;
+    use usiem::events::webproxy::WebProxyRuleCategory;
+
+    #[test]
+    fn test_category_names() {
+        assert_eq!(category_from_name("Hacking"), Some(WebProxyRuleCategory::Hacking));
+        assert_eq!(category_from_name("urlshorteners"), Some(WebProxyRuleCategory::URLShorteners));
+        assert_eq!(category_from_name("Hackers"), None);
+        let category = WebProxyRuleCategory::PersonalsDating;
+        assert_eq!(category_from_name(&category.to_string()), Some(category));
+    }
+
+    #[test]
+    fn test_mapping_formats() {
+        let text = CategoryMapping::from_text("# Docker groups\nhack = Hacking\nadditional: Spam\n\nporn Nudity # override").expect("Must work");
+        assert_eq!(text.category("hack"), WebProxyRuleCategory::Hacking);
+        assert_eq!(text.category("additional"), WebProxyRuleCategory::Spam);
+        assert_eq!(text.category("porn"), WebProxyRuleCategory::Nudity);
+        // Not configured: built-in defaults
+        assert_eq!(text.category("gamble"), WebProxyRuleCategory::Gambling);
+
+        let json = CategoryMapping::from_json(r#"{"hack": "Hacking", "Local_Block": "suspicious"}"#).expect("Must work");
+        assert_eq!(json.category("hack"), WebProxyRuleCategory::Hacking);
+        assert_eq!(json.category("local_block"), WebProxyRuleCategory::Suspicious);
+
+        let toml = CategoryMapping::from_toml("hack = \"Hacking\"\nadditional = \"Spam\"").expect("Must work");
+        assert_eq!(toml.category("additional"), WebProxyRuleCategory::Spam);
+
+        assert_eq!(CategoryMapping::from_text("hack = Hackers").err(), Some("Line 1: unknown category 'Hackers'".to_string()));
+        assert!(CategoryMapping::from_json(r#"{"hack": "Hackers"}"#).is_err());
+        assert!(CategoryMapping::from_toml("hack = ").is_err());
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("finance*", "finance"));
+        assert!(wildcard_match("finance*", "finance/banking"));
+        assert!(wildcard_match("hobby/games-*", "hobby/games-online"));
+        assert!(wildcard_match("*/games-*", "hobby/games-online"));
+        assert!(wildcard_match("*porn", "childporn"));
+        assert!(!wildcard_match("*porn", "porn_exceptions"));
+        assert!(!wildcard_match("hobby/games-*", "hobby/gardening"));
+        assert!(!wildcard_match("a*b*c", "a-c-b"));
+    }
+
+    #[test]
+    fn test_hierarchical_categories() {
+        let mapping = CategoryMapping::from_text("local/* = Suspicious\nlocal/ads* = WebAds\nlocal/ads/allowed = Informational\nfinance = Economy").expect("Must work");
+        // Exact beats wildcard, longest wildcard wins
+        assert_eq!(mapping.category("local/ads/allowed"), WebProxyRuleCategory::Informational);
+        assert_eq!(mapping.category("local/ads/other"), WebProxyRuleCategory::WebAds);
+        assert_eq!(mapping.category("local/malware"), WebProxyRuleCategory::Suspicious);
+        // Child to parent: profile subcategory, then the configured parent
+        assert_eq!(mapping.category("finance/trading"), WebProxyRuleCategory::Trading);
+        assert_eq!(mapping.category("finance/banking"), WebProxyRuleCategory::Economy);
+        assert_eq!(mapping.category("hobby/games-online/mmorpg"), WebProxyRuleCategory::Games);
+        // Names containing a category are not that category
+        assert_eq!(mapping.category("myfinance_exceptions"), WebProxyRuleCategory::Uncategorized);
+        assert_eq!(mapping.category("nohobby"), WebProxyRuleCategory::Uncategorized);
+        assert_eq!(mapping.category("automobile_allowed"), WebProxyRuleCategory::Uncategorized);
+    }
+
+    #[test]
+    fn test_mapping_layers() {
+        let mut mapping = CategoryMapping::from_text("hack = Hacking\nadditional = Spam").expect("Must work");
+        mapping.extend(CategoryMapping::from_text("additional = Suspicious").expect("Must work"));
+        assert_eq!(mapping.category("hack"), WebProxyRuleCategory::Hacking);
+        assert_eq!(mapping.category("additional"), WebProxyRuleCategory::Suspicious);
+        assert_eq!(mapping.get("porn"), None);
+        assert_eq!(mapping.category("porn"), WebProxyRuleCategory::Pornography);
+
+        let mapping = mapping.with_profile(CategoryProfile::UT1);
+        assert_eq!(mapping.category("hack"), WebProxyRuleCategory::Hacking);
+        assert_eq!(mapping.category("porn"), WebProxyRuleCategory::Uncategorized);
+        assert_eq!(mapping.category("adult"), WebProxyRuleCategory::Pornography);
+    }
+}
@@ -0,0 +1,631 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Error in a squidGuard.conf, with the position of the token that caused it
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConfigError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// `within` or `outside` a time rule
+#[derive(Debug, PartialEq, Clone)]
+pub enum TimeConstraint {
+    Within(String),
+    Outside(String),
+}
+
+/// `src` block: clients of a source group
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SourceGroup {
+    pub name: String,
+    pub time: Option<TimeConstraint>,
+    /// Addresses, networks (`10.0.0.0/8`, `10.0.0.0/255.0.0.0`) and ranges (`10.0.0.1-10.0.0.20`)
+    pub ips: Vec<String>,
+    pub iplists: Vec<String>,
+    pub users: Vec<String>,
+    pub userlists: Vec<String>,
+    pub domains: Vec<String>,
+    /// Other user sources like `ldapusersearch` or `execuserlist`, with the keyword
+    pub user_queries: Vec<(String, String)>,
+    pub log: Option<String>,
+}
+
+/// `dest` block: lists of a destination group
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DestinationGroup {
+    pub name: String,
+    pub time: Option<TimeConstraint>,
+    pub domainlist: Option<String>,
+    pub urllist: Option<String>,
+    pub expressionlist: Option<String>,
+    pub redirect: Option<String>,
+    pub rewrite: Option<String>,
+    pub log: Option<String>,
+}
+
+/// `time` block
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TimeRule {
+    pub name: String,
+    /// `weekly` and `date` entries with their arguments: `("weekly", "smtwhfa 08:00-17:00")`
+    pub entries: Vec<(String, String)>,
+}
+
+/// `rewrite` block
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RewriteRule {
+    pub name: String,
+    pub time: Option<TimeConstraint>,
+    /// Substitutions like `s@://www.google.com/search@://www.google.com/search?safe=active@i`
+    pub substitutions: Vec<String>,
+    pub log: Option<String>,
+}
+
+/// Entry of the `acl` block, for a source group or `default`
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AclRule {
+    pub source: String,
+    pub time: Option<TimeConstraint>,
+    /// Destination groups in order, negated with `!`, plus `all`, `none` or `in-addr`
+    pub pass: Vec<String>,
+    pub redirect: Option<String>,
+    pub rewrite: Option<String>,
+    pub log: Option<String>,
+    /// Rule applied outside of the time constraint
+    pub else_rule: Option<Box<AclRule>>,
+}
+
+/// Typed model of a squidGuard.conf
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SquidGuardConfig {
+    /// Top level settings: `dbhome`, `logdir`, `ldapbinddn`...
+    pub settings: BTreeMap<String, String>,
+    pub sources: Vec<SourceGroup>,
+    pub destinations: Vec<DestinationGroup>,
+    pub times: Vec<TimeRule>,
+    pub rewrites: Vec<RewriteRule>,
+    pub acls: Vec<AclRule>,
+}
+
+impl std::str::FromStr for SquidGuardConfig {
+    type Err = ConfigError;
+
+    fn from_str(content: &str) -> Result<SquidGuardConfig, ConfigError> {
+        let tokens = tokenize(content)?;
+        ConfigParser { tokens, pos: 0 }.parse()
+    }
+}
+
+impl SquidGuardConfig {
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SquidGuardConfig, ConfigError> {
+        match std::fs::read_to_string(path.as_ref()) {
+            Ok(content) => content.parse::<SquidGuardConfig>(),
+            Err(err) => Err(ConfigError {
+                line: 0,
+                column: 0,
+                message: format!("Cannot read {}: {}", path.as_ref().display(), err),
+            }),
+        }
+    }
+
+    pub fn dbhome(&self) -> Option<&str> {
+        self.settings.get("dbhome").map(|v| v.as_str())
+    }
+
+    pub fn logdir(&self) -> Option<&str> {
+        self.settings.get("logdir").map(|v| v.as_str())
+    }
+
+    pub fn source(&self, name: &str) -> Option<&SourceGroup> {
+        self.sources.iter().find(|v| v.name == name)
+    }
+
+    pub fn destination(&self, name: &str) -> Option<&DestinationGroup> {
+        self.destinations.iter().find(|v| v.name == name)
+    }
+
+    pub fn time(&self, name: &str) -> Option<&TimeRule> {
+        self.times.iter().find(|v| v.name == name)
+    }
+
+    pub fn rewrite(&self, name: &str) -> Option<&RewriteRule> {
+        self.rewrites.iter().find(|v| v.name == name)
+    }
+
+    /// ACL of the source group, or the `default` one
+    pub fn acl(&self, source: &str) -> Option<&AclRule> {
+        self.acls
+            .iter()
+            .find(|v| v.source == source)
+            .or_else(|| self.acls.iter().find(|v| v.source == "default"))
+    }
+
+    /// Redirect URL shown to the clients of the source group when a request is blocked.
+    /// The redirect of the destination group has precedence over the one of the ACL.
+    pub fn redirect_for(&self, source: &str, destination: Option<&str>) -> Option<&str> {
+        match self.acl(source) {
+            Some(acl) => self.rule_redirect(acl, destination),
+            None => destination.and_then(|v| self.destination(v)).and_then(|v| v.redirect.as_deref()),
+        }
+    }
+
+    /// Redirect URL of an ACL rule, or of its `else` branch, when the destination group blocks the request.
+    /// The redirect of the destination group has precedence, then the one of the rule and then the `default` ACL.
+    pub fn rule_redirect<'a>(&'a self, acl: &'a AclRule, destination: Option<&str>) -> Option<&'a str> {
+        if let Some(redirect) = destination.and_then(|v| self.destination(v)).and_then(|v| v.redirect.as_ref()) {
+            return Some(redirect);
+        }
+        match &acl.redirect {
+            Some(redirect) => Some(redirect),
+            None => self.acls.iter().find(|v| v.source == "default").and_then(|v| v.redirect.as_deref()),
+        }
+    }
+
+    /// Names of the destination groups that log to each file
+    pub fn destination_logs(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut logs: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for destination in &self.destinations {
+            if let Some(log) = &destination.log {
+                logs.entry(log.as_str()).or_default().push(destination.name.as_str());
+            }
+        }
+        logs
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum TokenKind {
+    Word(String),
+    Open,
+    Close,
+    NewLine,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+fn tokenize(content: &str) -> Result<Vec<Token>, ConfigError> {
+    let mut tokens = Vec::new();
+    for (line_number, line) in content.lines().enumerate() {
+        let line_number = line_number + 1;
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let (pos, c) = chars[i];
+            let column = line[..pos].chars().count() + 1;
+            if c == '#' {
+                break;
+            } else if c.is_whitespace() {
+                i += 1;
+            } else if c == '{' || c == '}' {
+                let kind = if c == '{' { TokenKind::Open } else { TokenKind::Close };
+                tokens.push(Token { kind, line: line_number, column });
+                i += 1;
+            } else if c == '"' {
+                let mut end = i + 1;
+                while end < chars.len() && chars[end].1 != '"' {
+                    end += 1;
+                }
+                if end >= chars.len() {
+                    return Err(ConfigError { line: line_number, column, message: "Unterminated quoted string".to_string() });
+                }
+                let text = &line[chars[i + 1].0..chars[end].0];
+                tokens.push(Token { kind: TokenKind::Word(text.to_string()), line: line_number, column });
+                i = end + 1;
+            } else {
+                let mut end = i;
+                while end < chars.len() && !chars[end].1.is_whitespace() && chars[end].1 != '{' && chars[end].1 != '}' {
+                    end += 1;
+                }
+                let end_pos = if end < chars.len() { chars[end].0 } else { line.len() };
+                tokens.push(Token { kind: TokenKind::Word(line[pos..end_pos].to_string()), line: line_number, column });
+                i = end;
+            }
+        }
+        tokens.push(Token { kind: TokenKind::NewLine, line: line_number, column: line.chars().count() + 1 });
+    }
+    Ok(tokens)
+}
+
+/// Statement inside a block: keyword and arguments until the end of the line
+struct Statement {
+    keyword: String,
+    args: Vec<String>,
+    line: usize,
+    column: usize,
+}
+
+impl Statement {
+    fn error(&self, message: &str) -> ConfigError {
+        ConfigError { line: self.line, column: self.column, message: message.to_string() }
+    }
+
+    /// Single argument, or the last one for `log anonymous file`
+    fn last_arg(&self) -> Result<String, ConfigError> {
+        match self.args.last() {
+            Some(arg) => Ok(arg.to_string()),
+            None => Err(self.error(&format!("'{}' needs an argument", self.keyword))),
+        }
+    }
+}
+
+struct ConfigParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ConfigParser {
+    fn parse(mut self) -> Result<SquidGuardConfig, ConfigError> {
+        let mut config = SquidGuardConfig::default();
+        loop {
+            self.skip_newlines();
+            let token = match self.tokens.get(self.pos) {
+                Some(token) => token.clone(),
+                None => return Ok(config),
+            };
+            let keyword = match &token.kind {
+                TokenKind::Word(word) => word.to_string(),
+                _ => return Err(unexpected(&token)),
+            };
+            self.pos += 1;
+            match keyword.as_str() {
+                "src" | "source" => {
+                    let (name, time) = self.block_header(&token)?;
+                    let mut source = SourceGroup { name, time, ..Default::default() };
+                    for statement in self.block()? {
+                        match statement.keyword.as_str() {
+                            "ip" => source.ips.extend(statement.args.iter().cloned()),
+                            "iplist" => source.iplists.push(statement.last_arg()?),
+                            "user" => source.users.extend(statement.args.iter().cloned()),
+                            "userlist" => source.userlists.push(statement.last_arg()?),
+                            "domain" => source.domains.extend(statement.args.iter().cloned()),
+                            "ldapusersearch" | "execuserlist" | "userquota" => {
+                                source.user_queries.push((statement.keyword.to_string(), statement.args.join(" ")))
+                            }
+                            "log" | "logfile" => source.log = Some(statement.last_arg()?),
+                            _ => return Err(statement.error(&format!("Unknown src option '{}'", statement.keyword))),
+                        }
+                    }
+                    config.sources.push(source);
+                }
+                "dest" | "destination" => {
+                    let (name, time) = self.block_header(&token)?;
+                    let mut destination = DestinationGroup { name, time, ..Default::default() };
+                    for statement in self.block()? {
+                        match statement.keyword.as_str() {
+                            "domainlist" => destination.domainlist = Some(statement.last_arg()?),
+                            "urllist" => destination.urllist = Some(statement.last_arg()?),
+                            "expressionlist" => destination.expressionlist = Some(statement.last_arg()?),
+                            "redirect" => destination.redirect = Some(statement.last_arg()?),
+                            "rewrite" => destination.rewrite = Some(statement.last_arg()?),
+                            "log" | "logfile" => destination.log = Some(statement.last_arg()?),
+                            _ => return Err(statement.error(&format!("Unknown dest option '{}'", statement.keyword))),
+                        }
+                    }
+                    config.destinations.push(destination);
+                }
+                "time" => {
+                    let (name, _) = self.block_header(&token)?;
+                    let mut time = TimeRule { name, ..Default::default() };
+                    for statement in self.block()? {
+                        match statement.keyword.as_str() {
+                            "weekly" | "date" => time.entries.push((statement.keyword.to_string(), statement.args.join(" "))),
+                            _ => return Err(statement.error(&format!("Unknown time option '{}'", statement.keyword))),
+                        }
+                    }
+                    config.times.push(time);
+                }
+                "rewrite" => {
+                    let (name, time) = self.block_header(&token)?;
+                    let mut rewrite = RewriteRule { name, time, ..Default::default() };
+                    for statement in self.block()? {
+                        if statement.keyword.starts_with("s@") {
+                            rewrite.substitutions.push(statement.keyword.to_string());
+                        } else if statement.keyword == "log" || statement.keyword == "logfile" {
+                            rewrite.log = Some(statement.last_arg()?);
+                        } else {
+                            return Err(statement.error(&format!("Unknown rewrite option '{}'", statement.keyword)));
+                        }
+                    }
+                    config.rewrites.push(rewrite);
+                }
+                "acl" => {
+                    self.expect_open()?;
+                    loop {
+                        self.skip_newlines();
+                        let token = match self.tokens.get(self.pos) {
+                            Some(token) => token.clone(),
+                            None => return Err(end_of_file(&self.tokens)),
+                        };
+                        if token.kind == TokenKind::Close {
+                            self.pos += 1;
+                            break;
+                        }
+                        let acl = self.acl_rule(&token)?;
+                        config.acls.push(acl);
+                    }
+                }
+                _ => {
+                    let args = self.line_args()?;
+                    if args.is_empty() {
+                        return Err(ConfigError { line: token.line, column: token.column, message: format!("'{}' needs a value", keyword) });
+                    }
+                    config.settings.insert(keyword, args.join(" "));
+                }
+            }
+        }
+    }
+
+    /// Name and optional `within`/`outside` time of `acl` entries
+    fn acl_rule(&mut self, token: &Token) -> Result<AclRule, ConfigError> {
+        let source = match &token.kind {
+            TokenKind::Word(word) => word.to_string(),
+            _ => return Err(unexpected(token)),
+        };
+        self.pos += 1;
+        let time = self.time_constraint(token)?;
+        let mut acl = AclRule { source, time, ..Default::default() };
+        self.acl_statements(&mut acl)?;
+        // `} else {`, the else can be in the next line. The newlines are kept if it is not there.
+        let start = self.pos;
+        self.skip_newlines();
+        match self.tokens.get(self.pos) {
+            Some(Token { kind: TokenKind::Word(word), .. }) if word == "else" => {}
+            _ => self.pos = start,
+        }
+        let next = self.tokens.get(self.pos).cloned();
+        if let Some(Token { kind: TokenKind::Word(word), .. }) = &next {
+            if word == "else" {
+                self.pos += 1;
+                let mut else_rule = AclRule { source: acl.source.to_string(), ..Default::default() };
+                self.acl_statements(&mut else_rule)?;
+                acl.else_rule = Some(Box::new(else_rule));
+            }
+        }
+        Ok(acl)
+    }
+
+    fn acl_statements(&mut self, acl: &mut AclRule) -> Result<(), ConfigError> {
+        self.expect_open()?;
+        for statement in self.block()? {
+            match statement.keyword.as_str() {
+                "pass" => acl.pass.extend(statement.args.iter().cloned()),
+                "redirect" => acl.redirect = Some(statement.last_arg()?),
+                "rewrite" => acl.rewrite = Some(statement.last_arg()?),
+                "log" | "logfile" => acl.log = Some(statement.last_arg()?),
+                _ => return Err(statement.error(&format!("Unknown acl option '{}'", statement.keyword))),
+            }
+        }
+        Ok(())
+    }
+
+    /// `name [within|outside time] {`
+    fn block_header(&mut self, token: &Token) -> Result<(String, Option<TimeConstraint>), ConfigError> {
+        let name = match self.tokens.get(self.pos) {
+            Some(Token { kind: TokenKind::Word(word), .. }) => word.to_string(),
+            _ => return Err(ConfigError { line: token.line, column: token.column, message: "Block without name".to_string() }),
+        };
+        self.pos += 1;
+        let time = self.time_constraint(token)?;
+        self.expect_open()?;
+        Ok((name, time))
+    }
+
+    fn time_constraint(&mut self, token: &Token) -> Result<Option<TimeConstraint>, ConfigError> {
+        let keyword = match self.tokens.get(self.pos) {
+            Some(Token { kind: TokenKind::Word(word), .. }) if word == "within" || word == "outside" => word.to_string(),
+            _ => return Ok(None),
+        };
+        self.pos += 1;
+        let name = match self.tokens.get(self.pos) {
+            Some(Token { kind: TokenKind::Word(word), .. }) => word.to_string(),
+            _ => return Err(ConfigError { line: token.line, column: token.column, message: format!("'{}' needs a time rule", keyword) }),
+        };
+        self.pos += 1;
+        if keyword == "within" {
+            Ok(Some(TimeConstraint::Within(name)))
+        } else {
+            Ok(Some(TimeConstraint::Outside(name)))
+        }
+    }
+
+    fn expect_open(&mut self) -> Result<(), ConfigError> {
+        self.skip_newlines();
+        match self.tokens.get(self.pos) {
+            Some(Token { kind: TokenKind::Open, .. }) => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(token) => Err(ConfigError { line: token.line, column: token.column, message: "Expected '{'".to_string() }),
+            None => Err(end_of_file(&self.tokens)),
+        }
+    }
+
+    /// Statements until the closing brace
+    fn block(&mut self) -> Result<Vec<Statement>, ConfigError> {
+        let mut statements = Vec::new();
+        loop {
+            self.skip_newlines();
+            let token = match self.tokens.get(self.pos) {
+                Some(token) => token.clone(),
+                None => return Err(end_of_file(&self.tokens)),
+            };
+            match token.kind {
+                TokenKind::Close => {
+                    self.pos += 1;
+                    return Ok(statements);
+                }
+                TokenKind::Word(keyword) => {
+                    self.pos += 1;
+                    let args = self.line_args()?;
+                    statements.push(Statement { keyword, args, line: token.line, column: token.column });
+                }
+                _ => return Err(unexpected(&token)),
+            }
+        }
+    }
+
+    /// Words until the end of the line or a closing brace
+    fn line_args(&mut self) -> Result<Vec<String>, ConfigError> {
+        let mut args = Vec::new();
+        while let Some(token) = self.tokens.get(self.pos) {
+            match &token.kind {
+                TokenKind::Word(word) => args.push(word.to_string()),
+                TokenKind::NewLine | TokenKind::Close => return Ok(args),
+                TokenKind::Open => return Err(unexpected(token)),
+            }
+            self.pos += 1;
+        }
+        Ok(args)
+    }
+
+    fn skip_newlines(&mut self) {
+        while let Some(Token { kind: TokenKind::NewLine, .. }) = self.tokens.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+}
+
+fn unexpected(token: &Token) -> ConfigError {
+    let text = match &token.kind {
+        TokenKind::Word(word) => word.to_string(),
+        TokenKind::Open => "{".to_string(),
+        TokenKind::Close => "}".to_string(),
+        TokenKind::NewLine => "end of line".to_string(),
+    };
+    ConfigError { line: token.line, column: token.column, message: format!("Unexpected '{}'", text) }
+}
+
+fn end_of_file(tokens: &[Token]) -> ConfigError {
+    let (line, column) = match tokens.last() {
+        Some(token) => (token.line, token.column),
+        None => (1, 1),
+    };
+    ConfigError { line, column, message: "Unexpected end of file, missing '}'".to_string() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SquidGuardConfig, TimeConstraint};
+
+    #[test]
+    fn test_docker_config() {
+        let config = include_str!("../../docker/squidGuard.conf").parse::<SquidGuardConfig>().expect("Must work");
+        assert_eq!(config.dbhome(), Some("/var/lib/squidguard/db"));
+        assert_eq!(config.logdir(), Some("/var/log/squid"));
+        assert_eq!(config.destinations.len(), 4);
+        let porn = config.destination("porn").expect("Must work");
+        assert_eq!(porn.domainlist, Some("porn".to_string()));
+        assert_eq!(porn.log, Some("deny.log".to_string()));
+        assert_eq!(config.destination_logs().get("deny.log"), Some(&vec!["porn", "hacking", "anonvpn", "lingerie"]));
+        let acl = config.acl("default").expect("Must work");
+        assert_eq!(acl.pass, vec!["!porn", "!hacking", "!anonvpn", "!lingerie", "all"]);
+        assert_eq!(config.redirect_for("students", Some("porn")), Some("http://127.0.0.1:80/denied.html"));
+    }
+
+    #[test]
+    fn test_full_config() {
+        let content = r#"
+dbhome /var/lib/squidguard/db
+logdir /var/log/squidguard
+
+time workhours {
+    weekly mtwhf 08:00-17:00
+    date 2021-12-25
+}
+
+src students {
+    ip 10.1.0.0/16 10.2.0.1-10.2.0.20
+    user "john smith" alice
+    userlist students.txt
+    log students.log
+}
+
+src staff within workhours {
+    ip 10.0.0.0/255.255.0.0
+}
+
+dest adult {
+    domainlist adult/domains
+    urllist adult/urls
+    redirect 302:http://proxy/blocked.html
+    log anonymous adult.log
+}
+
+rewrite safesearch {
+    s@(google\..*/search?.*q=.*)@&safe=active@i
+    log rewrite.log
+}
+
+acl {
+    staff within workhours {
+        pass !adult all
+    } else {
+        pass all
+    }
+    students {
+        pass !adult all
+        rewrite safesearch
+        redirect http://proxy/students.html
+    }
+    default {
+        pass none
+        redirect http://proxy/blocked.html
+    }
+}
+"#;
+        let config = content.parse::<SquidGuardConfig>().expect("Must work");
+        let students = config.source("students").expect("Must work");
+        assert_eq!(students.ips, vec!["10.1.0.0/16", "10.2.0.1-10.2.0.20"]);
+        assert_eq!(students.users, vec!["john smith", "alice"]);
+        assert_eq!(students.log, Some("students.log".to_string()));
+        assert_eq!(config.source("staff").expect("Must work").time, Some(TimeConstraint::Within("workhours".to_string())));
+        assert_eq!(config.time("workhours").expect("Must work").entries.len(), 2);
+        assert_eq!(config.destination("adult").expect("Must work").log, Some("adult.log".to_string()));
+        assert_eq!(config.rewrite("safesearch").expect("Must work").substitutions.len(), 1);
+        let staff = config.acl("staff").expect("Must work");
+        assert_eq!(staff.else_rule.as_ref().expect("Must work").pass, vec!["all"]);
+        assert_eq!(config.acl("students").expect("Must work").rewrite, Some("safesearch".to_string()));
+        assert_eq!(config.acl("guests").expect("Must work").source, "default");
+        assert_eq!(config.redirect_for("students", None), Some("http://proxy/students.html"));
+        assert_eq!(config.redirect_for("staff", None), Some("http://proxy/blocked.html"));
+        assert_eq!(config.redirect_for("students", Some("adult")), Some("302:http://proxy/blocked.html"));
+    }
+
+    #[test]
+    fn test_else_next_line() {
+        let content = "acl {\n    staff within workhours {\n        pass all\n    }\n    else\n    {\n        pass !porn all\n    }\n\n    default {\n        pass none\n    }\n}\n";
+        let config = content.parse::<SquidGuardConfig>().expect("Must work");
+        assert_eq!(config.acls.len(), 2);
+        let staff = config.acl("staff").expect("Must work");
+        assert_eq!(staff.else_rule.as_ref().expect("Must work").pass, vec!["!porn", "all"]);
+        assert_eq!(config.acls[1].source, "default");
+        assert!(config.acls[1].else_rule.is_none());
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let err = "dest porn {\n    domainlist porn\n    blocklist porn\n}".parse::<SquidGuardConfig>().expect_err("Must fail");
+        assert_eq!((err.line, err.column), (3, 5));
+        assert_eq!(err.to_string(), "line 3, column 5: Unknown dest option 'blocklist'");
+        let err = "dest porn {\n    domainlist porn\n".parse::<SquidGuardConfig>().expect_err("Must fail");
+        assert_eq!(err.message, "Unexpected end of file, missing '}'");
+        let err = "dest porn\n  domainlist porn\n}".parse::<SquidGuardConfig>().expect_err("Must fail");
+        assert_eq!((err.line, err.column), (2, 3));
+        let err = "src a {\n  user \"bob\n}".parse::<SquidGuardConfig>().expect_err("Must fail");
+        assert_eq!((err.line, err.column, err.message.as_str()), (2, 8, "Unterminated quoted string"));
+    }
+}
@@ -0,0 +1,310 @@
+use usiem::events::webproxy::WebProxyRuleCategory;
+use super::categories::resolve_category;
+
+/// Naming of the blacklist used to build the squidGuard destination groups
+#[derive(Debug, PartialEq, Clone)]
+pub enum CategoryProfile {
+    /// Shalla Secure Services list: http://www.shallalist.de/categories.html (discontinued)
+    Shalla,
+    /// Blacklists of the Université Toulouse Capitole: https://dsi.ut-capitole.fr/blacklists/
+    UT1,
+    /// Blacklists of the Multnomah Education Service District, shipped with squidGuard: http://squidguard.mesd.k12.or.us/blacklists.tgz
+    MESD,
+}
+
+impl Default for CategoryProfile {
+    fn default() -> CategoryProfile {
+        CategoryProfile::Shalla
+    }
+}
+
+impl std::str::FromStr for CategoryProfile {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<CategoryProfile, String> {
+        match name.to_lowercase().as_str() {
+            "shalla" | "shallalist" => Ok(CategoryProfile::Shalla),
+            "ut1" | "capitole" | "toulouse" => Ok(CategoryProfile::UT1),
+            "mesd" => Ok(CategoryProfile::MESD),
+            _ => Err(format!("Unknown category profile: {}", name)),
+        }
+    }
+}
+
+impl CategoryProfile {
+
+    /// Category names of the list and their equivalence
+    pub fn groups(&self) -> &'static [(&'static str, WebProxyRuleCategory)] {
+        match self {
+            CategoryProfile::Shalla => SHALLA,
+            CategoryProfile::UT1 => UT1,
+            CategoryProfile::MESD => MESD,
+        }
+    }
+
+    /// Category of a list name, `Uncategorized` if the list does not have it nor its parents
+    pub fn category(&self, group: &str) -> WebProxyRuleCategory {
+        let rules = self.groups().iter().map(|(name, category)| (*name, category));
+        match resolve_category(group, |level| super::categories::match_level(rules.clone(), level)) {
+            Some(category) => category,
+            None => WebProxyRuleCategory::Uncategorized,
+        }
+    }
+}
+
+/// Shalla categories with their full name and the short names used as destination groups.
+/// Subcategories without a finer category use the parent.
+pub static SHALLA: &[(&str, WebProxyRuleCategory)] = &[
+    ("adv", WebProxyRuleCategory::Spam),
+    ("aggressive", WebProxyRuleCategory::QuestionableLegality),
+    ("alcohol", WebProxyRuleCategory::Alcohol),
+    ("anonvpn", WebProxyRuleCategory::ProxyAvoidance),
+    ("automobile", WebProxyRuleCategory::Vehicles),
+    ("chat", WebProxyRuleCategory::OnlineChat),
+    ("costtraps", WebProxyRuleCategory::Phishing),
+    ("dating", WebProxyRuleCategory::PersonalsDating),
+    ("downloads", WebProxyRuleCategory::P2P),
+    ("drugs", WebProxyRuleCategory::Marijuana),
+    ("dynamic", WebProxyRuleCategory::DynamicDNSHost),
+    ("education/schools", WebProxyRuleCategory::Education),
+    ("schools", WebProxyRuleCategory::Education),
+    ("education", WebProxyRuleCategory::Education),
+    ("finance", WebProxyRuleCategory::Finance),
+    ("finance/realestate", WebProxyRuleCategory::RealEstate),
+    ("finance/trading", WebProxyRuleCategory::Trading),
+    ("fortunetelling", WebProxyRuleCategory::AlternativeSpirituality),
+    ("forum", WebProxyRuleCategory::Forums),
+    ("gamble", WebProxyRuleCategory::Gambling),
+    ("government", WebProxyRuleCategory::Government),
+    ("hacking", WebProxyRuleCategory::Hacking),
+    ("hobby", WebProxyRuleCategory::PersonalSites),
+    ("hobby/cooking", WebProxyRuleCategory::DailyLiving),
+    ("hobby/games-*", WebProxyRuleCategory::Games),
+    ("hobby/gardening", WebProxyRuleCategory::DailyLiving),
+    ("hobby/pets", WebProxyRuleCategory::DailyLiving),
+    ("homestyle", WebProxyRuleCategory::PersonalSites),
+    ("hospitals", WebProxyRuleCategory::Health),
+    ("imagehosting", WebProxyRuleCategory::WebHosting),
+    ("isp", WebProxyRuleCategory::InternetTelephony),
+    ("jobsearch", WebProxyRuleCategory::JobSearch),
+    ("library", WebProxyRuleCategory::Education),
+    ("military", WebProxyRuleCategory::Military),
+    ("models", WebProxyRuleCategory::PotentiallyAdult),
+    ("movies", WebProxyRuleCategory::VideoStreams),
+    ("music", WebProxyRuleCategory::AudioVideoClips),
+    ("news", WebProxyRuleCategory::News),
+    ("podcasts", WebProxyRuleCategory::RadioAudioStreams),
+    ("politics", WebProxyRuleCategory::PoliticalAdvocacy),
+    ("porn", WebProxyRuleCategory::Pornography),
+    ("radiotv", WebProxyRuleCategory::RadioAudioStreams),
+    ("recreation/humor", WebProxyRuleCategory::HumorJokes),
+    ("humor", WebProxyRuleCategory::HumorJokes),
+    ("recreation/martialarts", WebProxyRuleCategory::Sports),
+    ("martialarts", WebProxyRuleCategory::Sports),
+    ("recreation/restaurants", WebProxyRuleCategory::Restaurants),
+    ("restaurants", WebProxyRuleCategory::Restaurants),
+    ("recreation/sports", WebProxyRuleCategory::Sports),
+    ("sports", WebProxyRuleCategory::Sports),
+    ("recreation/travel", WebProxyRuleCategory::Travel),
+    ("travel", WebProxyRuleCategory::Travel),
+    ("recreation/wellness", WebProxyRuleCategory::Health),
+    ("wellness", WebProxyRuleCategory::Health),
+    ("recreation", WebProxyRuleCategory::Entertainment),
+    ("redirector", WebProxyRuleCategory::URLShorteners),
+    ("religion", WebProxyRuleCategory::Religion),
+    ("remotecontrol", WebProxyRuleCategory::RemoteAccess),
+    ("ringtones", WebProxyRuleCategory::InternetTelephony),
+    ("science/astronomy", WebProxyRuleCategory::Education),
+    ("astronomy", WebProxyRuleCategory::Education),
+    ("science/chemistry", WebProxyRuleCategory::Education),
+    ("chemistry", WebProxyRuleCategory::Education),
+    ("science", WebProxyRuleCategory::Education),
+    ("searchengines", WebProxyRuleCategory::SearchEngines),
+    ("sex/education", WebProxyRuleCategory::SexEducation),
+    ("sex/lingerie", WebProxyRuleCategory::IntimateApparel),
+    ("lingerie", WebProxyRuleCategory::IntimateApparel),
+    ("sex", WebProxyRuleCategory::SexualExpression),
+    ("shopping", WebProxyRuleCategory::Shopping),
+    ("socialnet", WebProxyRuleCategory::SocialNetworking),
+    ("spyware", WebProxyRuleCategory::MaliciousSources),
+    ("tracker", WebProxyRuleCategory::WebAds),
+    ("updatesites", WebProxyRuleCategory::SoftwareDownloads),
+    ("urlshortener", WebProxyRuleCategory::URLShorteners),
+    ("violence", WebProxyRuleCategory::Violence),
+    ("warez", WebProxyRuleCategory::CopyrightConcerns),
+    ("weapons", WebProxyRuleCategory::Weapons),
+    ("webmail", WebProxyRuleCategory::Email),
+    ("webphone", WebProxyRuleCategory::OnlineChat),
+    ("webradio", WebProxyRuleCategory::RadioAudioStreams),
+    ("webtv", WebProxyRuleCategory::VideoStreams),
+];
+
+/// UT1 categories: https://dsi.ut-capitole.fr/blacklists/index_en.php
+pub static UT1: &[(&str, WebProxyRuleCategory)] = &[
+    ("adult", WebProxyRuleCategory::Pornography),
+    ("agressif", WebProxyRuleCategory::QuestionableLegality),
+    ("arjel", WebProxyRuleCategory::Gambling),
+    ("associations_religieuses", WebProxyRuleCategory::Religion),
+    ("astrology", WebProxyRuleCategory::AlternativeSpirituality),
+    ("audio-video", WebProxyRuleCategory::AudioVideoClips),
+    ("bank", WebProxyRuleCategory::Finance),
+    ("bitcoin", WebProxyRuleCategory::Cryptocurrency),
+    ("blog", WebProxyRuleCategory::PersonalSites),
+    ("celebrity", WebProxyRuleCategory::Entertainment),
+    ("chat", WebProxyRuleCategory::OnlineChat),
+    ("child", WebProxyRuleCategory::ForKids),
+    ("cleaning", WebProxyRuleCategory::InformationSecurity),
+    ("cooking", WebProxyRuleCategory::DailyLiving),
+    ("cryptojacking", WebProxyRuleCategory::MaliciousSources),
+    ("dangerous_material", WebProxyRuleCategory::Weapons),
+    ("dating", WebProxyRuleCategory::PersonalsDating),
+    ("ddos", WebProxyRuleCategory::Hacking),
+    ("dialer", WebProxyRuleCategory::MaliciousSources),
+    ("doh", WebProxyRuleCategory::ProxyAvoidance),
+    ("download", WebProxyRuleCategory::SoftwareDownloads),
+    ("drogue", WebProxyRuleCategory::ControlledSubstances),
+    ("dynamic-dns", WebProxyRuleCategory::DynamicDNSHost),
+    ("educational_games", WebProxyRuleCategory::Education),
+    ("exceptions_liste_bu", WebProxyRuleCategory::Reference),
+    ("fakenews", WebProxyRuleCategory::Suspicious),
+    ("filehosting", WebProxyRuleCategory::FileStorage),
+    ("financial", WebProxyRuleCategory::Finance),
+    ("forums", WebProxyRuleCategory::Forums),
+    ("gambling", WebProxyRuleCategory::Gambling),
+    ("games", WebProxyRuleCategory::Games),
+    ("hacking", WebProxyRuleCategory::Hacking),
+    ("jobsearch", WebProxyRuleCategory::JobSearch),
+    ("lingerie", WebProxyRuleCategory::IntimateApparel),
+    ("liste_blanche", WebProxyRuleCategory::Informational),
+    ("liste_bu", WebProxyRuleCategory::Reference),
+    ("malware", WebProxyRuleCategory::MaliciousSources),
+    ("manga", WebProxyRuleCategory::Entertainment),
+    ("marketingware", WebProxyRuleCategory::PotentiallyUnwantedSoftware),
+    ("mixed_adult", WebProxyRuleCategory::PotentiallyAdult),
+    ("mobile-phone", WebProxyRuleCategory::Technology),
+    ("phishing", WebProxyRuleCategory::Phishing),
+    ("press", WebProxyRuleCategory::News),
+    ("publicite", WebProxyRuleCategory::WebAds),
+    ("radio", WebProxyRuleCategory::RadioAudioStreams),
+    ("reaffected", WebProxyRuleCategory::Placeholders),
+    ("redirector", WebProxyRuleCategory::ProxyAvoidance),
+    ("remote-control", WebProxyRuleCategory::RemoteAccess),
+    ("sect", WebProxyRuleCategory::AlternativeSpirituality),
+    ("sexual_education", WebProxyRuleCategory::SexEducation),
+    ("shopping", WebProxyRuleCategory::Shopping),
+    ("shortener", WebProxyRuleCategory::URLShorteners),
+    ("social_networks", WebProxyRuleCategory::SocialNetworking),
+    ("special", WebProxyRuleCategory::Informational),
+    ("sports", WebProxyRuleCategory::Sports),
+    ("stalkerware", WebProxyRuleCategory::PotentiallyUnwantedSoftware),
+    ("strict_redirector", WebProxyRuleCategory::ProxyAvoidance),
+    ("strong_redirector", WebProxyRuleCategory::ProxyAvoidance),
+    ("translation", WebProxyRuleCategory::Translation),
+    ("tricheur", WebProxyRuleCategory::QuestionableLegality),
+    ("tricheur_pix", WebProxyRuleCategory::QuestionableLegality),
+    ("update", WebProxyRuleCategory::SoftwareDownloads),
+    ("vpn", WebProxyRuleCategory::ProxyAvoidance),
+    ("warez", WebProxyRuleCategory::CopyrightConcerns),
+    ("webhosting", WebProxyRuleCategory::WebHosting),
+    ("webmail", WebProxyRuleCategory::Email),
+];
+
+/// MESD categories, the blacklists distributed with squidGuard
+pub static MESD: &[(&str, WebProxyRuleCategory)] = &[
+    ("ads", WebProxyRuleCategory::WebAds),
+    ("aggressive", WebProxyRuleCategory::QuestionableLegality),
+    ("audio-video", WebProxyRuleCategory::AudioVideoClips),
+    ("drugs", WebProxyRuleCategory::ControlledSubstances),
+    ("gambling", WebProxyRuleCategory::Gambling),
+    ("hacking", WebProxyRuleCategory::Hacking),
+    ("mail", WebProxyRuleCategory::Email),
+    ("porn", WebProxyRuleCategory::Pornography),
+    ("proxy", WebProxyRuleCategory::ProxyAvoidance),
+    ("redirector", WebProxyRuleCategory::ProxyAvoidance),
+    ("spyware", WebProxyRuleCategory::MaliciousSources),
+    ("suspect", WebProxyRuleCategory::Suspicious),
+    ("violence", WebProxyRuleCategory::Violence),
+    ("warez", WebProxyRuleCategory::CopyrightConcerns),
+];
+
+#[cfg(test)]
+mod test {
+    use super::CategoryProfile;
+    use usiem::events::webproxy::WebProxyRuleCategory;
+
+    static SHALLA_CATEGORIES: &[&str] = &[
+        "adv", "aggressive", "alcohol", "anonvpn", "automobile/bikes", "automobile/boats", "automobile/cars",
+        "automobile/planes", "chat", "costtraps", "dating", "downloads", "drugs", "dynamic", "education/schools",
+        "finance/banking", "finance/insurance", "finance/moneylending", "finance/other", "finance/realestate",
+        "finance/trading", "fortunetelling", "forum", "gamble", "government", "hacking", "hobby/cooking",
+        "hobby/games-misc", "hobby/games-online", "hobby/gardening", "hobby/pets", "homestyle", "hospitals",
+        "imagehosting", "isp", "jobsearch", "library", "military", "models", "movies", "music", "news", "podcasts",
+        "politics", "porn", "radiotv", "recreation/humor", "recreation/martialarts", "recreation/restaurants",
+        "recreation/sports", "recreation/travel", "recreation/wellness", "redirector", "religion", "remotecontrol",
+        "ringtones", "science/astronomy", "science/chemistry", "searchengines", "sex/education", "sex/lingerie",
+        "shopping", "socialnet", "spyware", "tracker", "updatesites", "urlshortener", "violence", "warez", "weapons",
+        "webmail", "webphone", "webradio", "webtv",
+    ];
+
+    static UT1_CATEGORIES: &[&str] = &[
+        "adult", "agressif", "arjel", "associations_religieuses", "astrology", "audio-video", "bank", "bitcoin",
+        "blog", "celebrity", "chat", "child", "cleaning", "cooking", "cryptojacking", "dangerous_material", "dating",
+        "ddos", "dialer", "doh", "download", "drogue", "dynamic-dns", "educational_games", "exceptions_liste_bu",
+        "fakenews", "filehosting", "financial", "forums", "gambling", "games", "hacking", "jobsearch", "lingerie",
+        "liste_blanche", "liste_bu", "malware", "manga", "marketingware", "mixed_adult", "mobile-phone", "phishing",
+        "press", "publicite", "radio", "reaffected", "redirector", "remote-control", "sect", "sexual_education",
+        "shopping", "shortener", "social_networks", "special", "sports", "stalkerware", "strict_redirector",
+        "strong_redirector", "translation", "tricheur", "tricheur_pix", "update", "vpn", "warez", "webhosting",
+        "webmail",
+    ];
+
+    static MESD_CATEGORIES: &[&str] = &[
+        "ads", "aggressive", "audio-video", "drugs", "gambling", "hacking", "mail", "porn", "proxy", "redirector",
+        "spyware", "suspect", "violence", "warez",
+    ];
+
+    #[test]
+    fn test_shalla_profile() {
+        let profile = "Shalla".parse::<CategoryProfile>().expect("Must work");
+        for category in SHALLA_CATEGORIES {
+            assert_ne!(profile.category(category), WebProxyRuleCategory::Uncategorized, "{}", category);
+        }
+        assert_eq!(profile.category("porn"), WebProxyRuleCategory::Pornography);
+        assert_eq!(profile.category("sex/lingerie"), WebProxyRuleCategory::IntimateApparel);
+        assert_eq!(profile.category("adult"), WebProxyRuleCategory::Uncategorized);
+        assert_eq!(profile.category("finance/banking"), WebProxyRuleCategory::Finance);
+        assert_eq!(profile.category("finance/trading"), WebProxyRuleCategory::Trading);
+        assert_eq!(profile.category("hobby/games-online"), WebProxyRuleCategory::Games);
+        assert_eq!(profile.category("hobby/pets"), WebProxyRuleCategory::DailyLiving);
+        assert_eq!(profile.category("automobile/cars"), WebProxyRuleCategory::Vehicles);
+    }
+
+    #[test]
+    fn test_ut1_profile() {
+        let profile = "ut1".parse::<CategoryProfile>().expect("Must work");
+        assert_eq!("capitole".parse::<CategoryProfile>(), Ok(CategoryProfile::UT1));
+        assert!("dmoz".parse::<CategoryProfile>().is_err());
+        for category in UT1_CATEGORIES {
+            assert_ne!(profile.category(category), WebProxyRuleCategory::Uncategorized, "{}", category);
+        }
+        assert_eq!(profile.groups().len(), UT1_CATEGORIES.len());
+        assert_eq!(profile.category("adult"), WebProxyRuleCategory::Pornography);
+        assert_eq!(profile.category("mixed_adult"), WebProxyRuleCategory::PotentiallyAdult);
+        assert_eq!(profile.category("cryptojacking"), WebProxyRuleCategory::MaliciousSources);
+        assert_eq!(profile.category("sect"), WebProxyRuleCategory::AlternativeSpirituality);
+        assert_eq!(profile.category("porn"), WebProxyRuleCategory::Uncategorized);
+    }
+
+    #[test]
+    fn test_mesd_profile() {
+        let profile = "MESD".parse::<CategoryProfile>().expect("Must work");
+        for category in MESD_CATEGORIES {
+            assert_ne!(profile.category(category), WebProxyRuleCategory::Uncategorized, "{}", category);
+        }
+        assert_eq!(profile.groups().len(), MESD_CATEGORIES.len());
+        assert_eq!(profile.category("porn"), WebProxyRuleCategory::Pornography);
+        assert_eq!(profile.category("mail"), WebProxyRuleCategory::Email);
+        assert_eq!(profile.category("suspect"), WebProxyRuleCategory::Suspicious);
+        assert_eq!(profile.category("adult"), WebProxyRuleCategory::Uncategorized);
+    }
+}
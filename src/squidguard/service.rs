@@ -0,0 +1,227 @@
+use std::borrow::Cow;
+use usiem::events::field::SiemField;
+use usiem::events::field_dictionary;
+use usiem::events::SiemLog;
+use crate::fields;
+
+/// Type of list loaded from the squidGuard database
+#[derive(Debug, PartialEq, Clone)]
+pub enum SquidGuardList {
+    Domains,
+    Urls,
+    Expressions,
+    /// A dbfile whose name does not tell the list type
+    Unknown,
+}
+
+impl std::fmt::Display for SquidGuardList {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SquidGuardList::Domains => write!(f, "domains"),
+            SquidGuardList::Urls => write!(f, "urls"),
+            SquidGuardList::Expressions => write!(f, "expressions"),
+            SquidGuardList::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Lines written by squidGuard about its own state, everything that is not a `Request(` line
+#[derive(Debug, PartialEq, Clone)]
+pub enum SquidGuardServiceEvent {
+    /// `squidGuard 1.6.0 started (1613260801.123)`
+    Start { version: Option<String> },
+    /// `squidGuard ready for requests (1613260801.125)`
+    Ready,
+    /// `squidGuard stopped (1613260805.789)`
+    Stop,
+    /// `got sigHUP reload config`
+    Reload,
+    /// `Going into emergency mode`. squidGuard stops filtering and lets all the requests pass.
+    EmergencyMode,
+    /// `init domainlist /var/lib/squidguard/db/porn/domains`, `loading dbfile .../urls.db`
+    DatabaseLoad {
+        list: SquidGuardList,
+        path: String,
+        domains: Option<u64>,
+        urls: Option<u64>,
+    },
+    /// `Error db_open: ...`, `sgReadConfig: ... error`
+    Error,
+    /// Other informative lines: `New setting: dbhome: /var/lib/squidguard/db`
+    Info,
+}
+
+impl SquidGuardServiceEvent {
+    pub fn from_message(message: &str) -> SquidGuardServiceEvent {
+        let lower = message.to_lowercase();
+        // The list paths can have any word: /db/terror/domains
+        for prefix in &["init domainlist ", "init urllist ", "init expressionlist ", "loading dbfile ", "create new dbfile "] {
+            if lower.starts_with(prefix) {
+                return database_load(&message[prefix.len()..]);
+            }
+        }
+        if lower.contains("emergency mode") {
+            return SquidGuardServiceEvent::EmergencyMode;
+        }
+        if has_word(&lower, "error") || has_word(&lower, "fatal") {
+            return SquidGuardServiceEvent::Error;
+        }
+        if lower.starts_with("squidguard") {
+            if lower.contains("ready for requests") {
+                return SquidGuardServiceEvent::Ready;
+            } else if lower.contains(" started") {
+                let version = message
+                    .split(' ')
+                    .nth(1)
+                    .filter(|v| v.starts_with(|c: char| c.is_ascii_digit()))
+                    .map(|v| v.to_string());
+                return SquidGuardServiceEvent::Start { version };
+            } else if lower.contains(" stopped") {
+                return SquidGuardServiceEvent::Stop;
+            }
+        }
+        if has_word(&lower, "sighup") || has_word(&lower, "reload") {
+            return SquidGuardServiceEvent::Reload;
+        }
+        SquidGuardServiceEvent::Info
+    }
+
+    /// Value for the `event.action` field
+    pub fn action(&self) -> &'static str {
+        match self {
+            SquidGuardServiceEvent::Start { .. } => "start",
+            SquidGuardServiceEvent::Ready => "ready",
+            SquidGuardServiceEvent::Stop => "stop",
+            SquidGuardServiceEvent::Reload => "reload",
+            SquidGuardServiceEvent::EmergencyMode => "emergency_mode",
+            SquidGuardServiceEvent::DatabaseLoad { .. } => "db_load",
+            SquidGuardServiceEvent::Error => "error",
+            SquidGuardServiceEvent::Info => "info",
+        }
+    }
+
+    pub fn add_fields(&self, log: &mut SiemLog, message: &str) {
+        log.add_field(field_dictionary::EVENT_ACTION, SiemField::from_str(self.action()));
+        match self {
+            SquidGuardServiceEvent::Start { version: Some(version) } => {
+                log.add_field(fields::SERVICE_VERSION, SiemField::Text(Cow::Owned(version.to_string())));
+            }
+            SquidGuardServiceEvent::DatabaseLoad { list, path, domains, urls } => {
+                log.add_field(fields::SQUIDGUARD_DB_LIST, SiemField::Text(Cow::Owned(list.to_string())));
+                log.add_field(fields::FILE_PATH, SiemField::Text(Cow::Owned(path.to_string())));
+                if let Some(domains) = domains {
+                    log.add_field(fields::SQUIDGUARD_DB_DOMAINS, SiemField::U64(*domains));
+                }
+                if let Some(urls) = urls {
+                    log.add_field(fields::SQUIDGUARD_DB_URLS, SiemField::U64(*urls));
+                }
+            }
+            SquidGuardServiceEvent::Error => {
+                log.add_field(fields::ERROR_MESSAGE, SiemField::Text(Cow::Owned(message.to_string())));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Checks if the text has the word, ignoring the punctuation around it but not inside paths: `/var/log/error`
+fn has_word(text: &str, word: &str) -> bool {
+    text.split_whitespace()
+        .any(|v| v.trim_matches(|c: char| !c.is_alphanumeric()) == word)
+}
+
+/// Path of the list and the entry counts if written: `/db/porn/domains (1234 domains, 56 urls)`
+fn database_load(text: &str) -> SquidGuardServiceEvent {
+    let text = text.trim();
+    let path = match text.find(" ") {
+        Some(pos) => &text[..pos],
+        None => text,
+    };
+    let file_name = match path.rfind("/") {
+        Some(pos) => &path[pos + 1..],
+        None => path,
+    };
+    let list = if file_name.starts_with("domains") {
+        SquidGuardList::Domains
+    } else if file_name.starts_with("urls") {
+        SquidGuardList::Urls
+    } else if file_name.starts_with("expressions") {
+        SquidGuardList::Expressions
+    } else {
+        SquidGuardList::Unknown
+    };
+    let mut domains = None;
+    let mut urls = None;
+    let words: Vec<&str> = text[path.len()..]
+        .split([' ', ',', '(', ')'])
+        .filter(|v| !v.is_empty())
+        .collect();
+    for pair in words.windows(2) {
+        let count = match pair[0].parse::<u64>() {
+            Ok(count) => count,
+            Err(_) => continue,
+        };
+        let unit = pair[1].to_lowercase();
+        if unit.starts_with("domain") {
+            domains = Some(count);
+        } else if unit.starts_with("url") {
+            urls = Some(count);
+        } else if unit.starts_with("entr") {
+            match list {
+                SquidGuardList::Domains => domains = Some(count),
+                SquidGuardList::Urls => urls = Some(count),
+                _ => {}
+            }
+        }
+    }
+    SquidGuardServiceEvent::DatabaseLoad {
+        list,
+        path: path.to_string(),
+        domains,
+        urls,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SquidGuardList, SquidGuardServiceEvent};
+
+    #[test]
+    fn test_service_messages() {
+        assert_eq!(SquidGuardServiceEvent::from_message("squidGuard 1.6.0 started (1613260801.123)"), SquidGuardServiceEvent::Start { version: Some("1.6.0".to_string()) });
+        assert_eq!(SquidGuardServiceEvent::from_message("squidGuard ready for requests (1613260801.125)"), SquidGuardServiceEvent::Ready);
+        assert_eq!(SquidGuardServiceEvent::from_message("squidGuard stopped (1613260805.789)"), SquidGuardServiceEvent::Stop);
+        assert_eq!(SquidGuardServiceEvent::from_message("got sigHUP reload config"), SquidGuardServiceEvent::Reload);
+        assert_eq!(SquidGuardServiceEvent::from_message("Going into emergency mode"), SquidGuardServiceEvent::EmergencyMode);
+        assert_eq!(SquidGuardServiceEvent::from_message("Error db_open: Invalid argument"), SquidGuardServiceEvent::Error);
+        assert_eq!(SquidGuardServiceEvent::from_message("New setting: dbhome: /var/lib/squidguard/db"), SquidGuardServiceEvent::Info);
+        assert_eq!(SquidGuardServiceEvent::from_message("sgReadConfig: line 12: FATAL: unknown source"), SquidGuardServiceEvent::Error);
+        assert_eq!(SquidGuardServiceEvent::from_message("New setting: logdir: /var/log/error"), SquidGuardServiceEvent::Info);
+        assert_eq!(SquidGuardServiceEvent::from_message("New setting: logdir: /var/log/reloaded"), SquidGuardServiceEvent::Info);
+    }
+
+    #[test]
+    fn test_database_load() {
+        assert_eq!(
+            SquidGuardServiceEvent::from_message("init domainlist /var/lib/squidguard/db/porn/domains"),
+            SquidGuardServiceEvent::DatabaseLoad { list: SquidGuardList::Domains, path: "/var/lib/squidguard/db/porn/domains".to_string(), domains: None, urls: None }
+        );
+        assert_eq!(
+            SquidGuardServiceEvent::from_message("loading dbfile /var/lib/squidguard/db/porn/urls.db (5230 entries)"),
+            SquidGuardServiceEvent::DatabaseLoad { list: SquidGuardList::Urls, path: "/var/lib/squidguard/db/porn/urls.db".to_string(), domains: None, urls: Some(5230) }
+        );
+        assert_eq!(
+            SquidGuardServiceEvent::from_message("loading dbfile /var/lib/squidguard/db/porn (1234 domains, 56 urls)"),
+            SquidGuardServiceEvent::DatabaseLoad { list: SquidGuardList::Unknown, path: "/var/lib/squidguard/db/porn".to_string(), domains: Some(1234), urls: Some(56) }
+        );
+        // Category names that contain error or reload are not errors nor reloads
+        assert_eq!(
+            SquidGuardServiceEvent::from_message("init domainlist /var/lib/squidguard/db/terror/domains"),
+            SquidGuardServiceEvent::DatabaseLoad { list: SquidGuardList::Domains, path: "/var/lib/squidguard/db/terror/domains".to_string(), domains: None, urls: None }
+        );
+        assert_eq!(
+            SquidGuardServiceEvent::from_message("loading dbfile /var/lib/squidguard/db/reload/urls.db"),
+            SquidGuardServiceEvent::DatabaseLoad { list: SquidGuardList::Urls, path: "/var/lib/squidguard/db/reload/urls.db".to_string(), domains: None, urls: None }
+        );
+    }
+}
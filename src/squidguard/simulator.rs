@@ -0,0 +1,581 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use chrono::{Datelike, NaiveDateTime, Weekday};
+use usiem::events::field::SiemIp;
+use usiem::events::SiemLog;
+use crate::categorizer::DomainCategorizer;
+use crate::matching::{ip_matches, time_range_matches};
+use crate::timezone::LogTimezone;
+use super::categories::CategoryMapping;
+use super::config::{AclRule, SourceGroup, SquidGuardConfig, TimeConstraint};
+use super::{parse_log_internal, parse_url, SquidGuardAction};
+
+/// Decision squidGuard would take for a request
+#[derive(Debug, PartialEq, Clone)]
+pub struct PolicyVerdict {
+    /// Source group of the client, `default` if none matches
+    pub source: String,
+    /// ACL entry applied: the source group or `default`
+    pub acl: String,
+    /// The `else` branch of the ACL was applied because the time constraint did not match
+    pub acl_else: bool,
+    /// Entry of the `pass` list that decided: a destination group, `all`, `none` or `in-addr`
+    pub destination: Option<String>,
+    pub action: SquidGuardAction,
+    /// Block page with the `%a`, `%i`, `%n`, `%s`, `%t` and `%u` placeholders replaced
+    pub redirect: Option<String>,
+    /// Rewrite rule applied
+    pub rewrite: Option<String>,
+}
+
+/// Evaluates requests against a squidGuard.conf and its lists without a running squidGuard,
+/// to test policy changes against historical access logs.
+///
+/// Expression lists are not evaluated and the rewrite substitutions are matched literally, not as regular expressions.
+#[derive(Clone)]
+pub struct PolicySimulator {
+    config: SquidGuardConfig,
+    timezone: LogTimezone,
+    categories: CategoryMapping,
+    destinations: BTreeMap<String, DomainCategorizer>,
+    source_ips: BTreeMap<String, Vec<String>>,
+    source_users: BTreeMap<String, Vec<String>>,
+}
+
+impl PolicySimulator {
+    pub fn new(config: SquidGuardConfig) -> PolicySimulator {
+        let mut source_ips = BTreeMap::new();
+        let mut source_users = BTreeMap::new();
+        for source in &config.sources {
+            source_ips.insert(source.name.to_string(), source.ips.clone());
+            source_users.insert(source.name.to_string(), source.users.clone());
+        }
+        PolicySimulator {
+            config,
+            timezone: LogTimezone::Utc,
+            categories: CategoryMapping::new(),
+            destinations: BTreeMap::new(),
+            source_ips,
+            source_users,
+        }
+    }
+    /// Timezone of the proxy, used by the time rules and the log dates. Defaults to UTC.
+    pub fn with_timezone(mut self, timezone: LogTimezone) -> PolicySimulator {
+        self.timezone = timezone;
+        self
+    }
+    /// Categories of the destination groups named differently than the built-in defaults
+    pub fn with_categories(mut self, categories: CategoryMapping) -> PolicySimulator {
+        self.categories = categories;
+        self
+    }
+
+    pub fn config(&self) -> &SquidGuardConfig {
+        &self.config
+    }
+
+    /// Adds the entries of a `domains` file to a destination group
+    pub fn add_domains(&mut self, destination: &str, content: &str) {
+        let category = self.categories.category(destination);
+        self.destinations.entry(destination.to_string()).or_default().add_domains(destination, category, content);
+    }
+
+    /// Adds the entries of an `urls` file to a destination group
+    pub fn add_urls(&mut self, destination: &str, content: &str) {
+        let category = self.categories.category(destination);
+        self.destinations.entry(destination.to_string()).or_default().add_urls(destination, category, content);
+    }
+
+    /// Adds the entries of an `iplist` file to a source group
+    pub fn add_ips(&mut self, source: &str, content: &str) {
+        self.source_ips.entry(source.to_string()).or_default().extend(list_entries(content));
+    }
+
+    /// Adds the entries of an `userlist` file to a source group. Lines can have a comment after `:`.
+    pub fn add_users(&mut self, source: &str, content: &str) {
+        let users = list_entries(content).map(|v| match v.find(':') {
+            Some(pos) => v[..pos].trim().to_string(),
+            None => v,
+        });
+        self.source_users.entry(source.to_string()).or_default().extend(users);
+    }
+
+    /// Loads the lists of the config. Relative paths are read from `dbhome`.
+    pub fn load_lists(&mut self) -> Result<(), String> {
+        let dbhome = self.config.dbhome().map(PathBuf::from).unwrap_or_default();
+        let destinations = self.config.destinations.clone();
+        for destination in &destinations {
+            if let Some(path) = &destination.domainlist {
+                let content = read_list(&dbhome, path)?;
+                self.add_domains(&destination.name, &content);
+            }
+            if let Some(path) = &destination.urllist {
+                let content = read_list(&dbhome, path)?;
+                self.add_urls(&destination.name, &content);
+            }
+        }
+        let sources = self.config.sources.clone();
+        for source in &sources {
+            for path in &source.iplists {
+                let content = read_list(&dbhome, path)?;
+                self.add_ips(&source.name, &content);
+            }
+            for path in &source.userlists {
+                let content = read_list(&dbhome, path)?;
+                self.add_users(&source.name, &content);
+            }
+        }
+        Ok(())
+    }
+
+    /// Verdict for a request. `url` is the URL received from Squid: absolute, or `host:port` for CONNECT requests.
+    /// `timestamp` is in milliseconds since epoch.
+    pub fn verdict(&self, client_ip: &str, user: &str, url: &str, timestamp: i64) -> Result<PolicyVerdict, String> {
+        let client_ip = match client_ip.parse::<IpAddr>() {
+            Ok(ip) => ip,
+            Err(_) => return Err(format!("Invalid client IP {}", client_ip)),
+        };
+        let date = match self.timezone.local_datetime(timestamp) {
+            Some(date) => date,
+            None => return Err(format!("Invalid timestamp {}", timestamp)),
+        };
+        let (_, domain, path, _) = parse_url(url).map_err(|e| e.to_string())?;
+        let source = self
+            .config
+            .sources
+            .iter()
+            .find(|source| self.time_matches(&source.time, &date) && self.source_matches(source, &client_ip, user))
+            .map(|source| source.name.as_str())
+            .unwrap_or("default");
+        let mut verdict = PolicyVerdict {
+            source: source.to_string(),
+            acl: String::new(),
+            acl_else: false,
+            destination: None,
+            action: SquidGuardAction::Pass,
+            redirect: None,
+            rewrite: None,
+        };
+        let acl = match self.acl(source, &date) {
+            Some((acl, acl_else)) => {
+                verdict.acl = acl.source.to_string();
+                verdict.acl_else = acl_else;
+                acl
+            }
+            // Without ACLs squidGuard lets everything pass
+            None => return Ok(verdict),
+        };
+        let mut blocked = false;
+        for entry in &acl.pass {
+            let (negated, name) = match entry.strip_prefix('!') {
+                Some(name) => (true, name),
+                None => (false, entry.as_str()),
+            };
+            let matches = match name {
+                "all" => true,
+                "none" => !negated,
+                "in-addr" => domain.parse::<IpAddr>().is_ok(),
+                _ => self.destination_matches(name, domain, path, &date),
+            };
+            if matches {
+                blocked = negated || name == "none";
+                verdict.destination = Some(name.to_string());
+                break;
+            }
+        }
+        if blocked {
+            let destination = verdict.destination.as_deref().and_then(|v| self.config.destination(v));
+            let rewrite = destination.and_then(|v| v.rewrite.as_deref());
+            if let Some((rewrite, target)) = rewrite.and_then(|v| self.rewrite(v, url, &date)) {
+                verdict.rewrite = Some(rewrite);
+                verdict.action = SquidGuardAction::Rewrite(Some(target));
+            } else {
+                verdict.action = SquidGuardAction::Redirect;
+                verdict.redirect = self
+                    .config
+                    .rule_redirect(acl, verdict.destination.as_deref())
+                    .map(|v| fill_redirect(v, &client_ip.to_string(), user, &verdict.source, verdict.destination.as_deref().unwrap_or("-"), url));
+            }
+        } else if let Some((rewrite, target)) = acl.rewrite.as_deref().and_then(|v| self.rewrite(v, url, &date)) {
+            verdict.rewrite = Some(rewrite);
+            verdict.action = SquidGuardAction::Rewrite(Some(target));
+        }
+        Ok(verdict)
+    }
+
+    /// Simulates a request and returns the log squidGuard would write, parsed like `squidguard::parse_log`.
+    /// URLs without scheme are taken as CONNECT requests, the others as GET.
+    pub fn simulate(&self, client_ip: &str, user: &str, url: &str, timestamp: i64) -> Result<SiemLog, String> {
+        let verdict = self.verdict(client_ip, user, url, timestamp)?;
+        let log_line = self.log_line(&verdict, client_ip, user, url, timestamp)?;
+        let origin = SiemIp::from_ip_str(client_ip).unwrap_or(SiemIp::V4(0));
+        let log = SiemLog::new(log_line, timestamp, origin);
+        match parse_log_internal(log, &self.timezone, &self.categories) {
+            Ok(log) => Ok(log),
+            Err(_) => Err(format!("Cannot simulate request to {}", url)),
+        }
+    }
+
+    /// `Request(` line of the verdict
+    pub fn log_line(&self, verdict: &PolicyVerdict, client_ip: &str, user: &str, url: &str, timestamp: i64) -> Result<String, String> {
+        let date = match self.timezone.local_datetime(timestamp) {
+            Some(date) => date,
+            None => return Err(format!("Invalid timestamp {}", timestamp)),
+        };
+        let method = if url.contains("://") { "GET" } else { "CONNECT" };
+        let user = if user.is_empty() { "-".to_string() } else { user.replace('%', "%25").replace(' ', "%20") };
+        let action = match &verdict.action {
+            SquidGuardAction::Redirect => "REDIRECT".to_string(),
+            SquidGuardAction::Pass => "PASS".to_string(),
+            SquidGuardAction::Rewrite(Some(target)) => format!("REWRITE {}", target),
+            SquidGuardAction::Rewrite(None) => "REWRITE".to_string(),
+            SquidGuardAction::Unknown(action) => action.to_string(),
+        };
+        Ok(format!(
+            "{} [0] Request({}/{}/{}) {} {}/- {} {} {}",
+            date.format("%Y-%m-%d %H:%M:%S"),
+            verdict.acl,
+            verdict.destination.as_deref().unwrap_or("-"),
+            verdict.rewrite.as_deref().unwrap_or("-"),
+            url,
+            client_ip,
+            user,
+            method,
+            action
+        ))
+    }
+
+    fn source_matches(&self, source: &SourceGroup, client_ip: &IpAddr, user: &str) -> bool {
+        let ip_match = self.source_ips.get(&source.name).map(|ips| ips.iter().any(|v| ip_matches(v, client_ip))).unwrap_or(false);
+        let user_match = !user.is_empty()
+            && user != "-"
+            && self.source_users.get(&source.name).map(|users| users.iter().any(|v| v == user)).unwrap_or(false);
+        ip_match || user_match
+    }
+
+    /// ACL of the source group and whether the `else` branch applies
+    fn acl(&self, source: &str, date: &NaiveDateTime) -> Option<(&AclRule, bool)> {
+        let acl = self.config.acl(source)?;
+        if self.time_matches(&acl.time, date) {
+            return Some((acl, false));
+        }
+        match &acl.else_rule {
+            Some(else_rule) => Some((else_rule, true)),
+            None => self.config.acls.iter().find(|v| v.source == "default" && v.source != acl.source).map(|v| (v, false)),
+        }
+    }
+
+    fn destination_matches(&self, name: &str, domain: &str, path: &str, date: &NaiveDateTime) -> bool {
+        if let Some(destination) = self.config.destination(name) {
+            if !self.time_matches(&destination.time, date) {
+                return false;
+            }
+        }
+        match self.destinations.get(name) {
+            Some(categorizer) => categorizer.categorize(domain, path).is_some(),
+            None => false,
+        }
+    }
+
+    /// Name of the rule and the new URL if a substitution changes it
+    fn rewrite(&self, name: &str, url: &str, date: &NaiveDateTime) -> Option<(String, String)> {
+        let rewrite = self.config.rewrite(name)?;
+        if !self.time_matches(&rewrite.time, date) {
+            return None;
+        }
+        rewrite
+            .substitutions
+            .iter()
+            .filter_map(|v| substitute(v, url))
+            .next()
+            .map(|target| (name.to_string(), target))
+    }
+
+    fn time_matches(&self, constraint: &Option<TimeConstraint>, date: &NaiveDateTime) -> bool {
+        let (name, within) = match constraint {
+            Some(TimeConstraint::Within(name)) => (name, true),
+            Some(TimeConstraint::Outside(name)) => (name, false),
+            None => return true,
+        };
+        let matches = match self.config.time(name) {
+            Some(time) => time.entries.iter().any(|(kind, args)| time_entry_matches(kind, args, date)),
+            None => false,
+        };
+        matches == within
+    }
+}
+
+fn read_list(dbhome: &Path, path: &str) -> Result<String, String> {
+    let path = dbhome.join(path);
+    match std::fs::read_to_string(&path) {
+        Ok(content) => Ok(content),
+        Err(err) => Err(format!("Cannot read {}: {}", path.display(), err)),
+    }
+}
+
+fn list_entries(content: &str) -> impl Iterator<Item = String> + '_ {
+    content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+}
+
+/// `weekly smtwhfa 08:00-17:00` or `date 2021-12-24-2021-12-26 08:00-12:00`. Days and dates accept `*`.
+fn time_entry_matches(kind: &str, args: &str, date: &NaiveDateTime) -> bool {
+    let (ranges, specs): (Vec<&str>, Vec<&str>) = args.split_whitespace().partition(|v| v.contains(':'));
+    let time = date.time();
+    let time_match = ranges.is_empty() || ranges.iter().any(|range| time_range_matches(range, &time));
+    let day_match = match kind {
+        "weekly" => specs.iter().any(|v| weekday_matches(v, date.weekday())),
+        "date" => specs.iter().any(|v| date_matches(v, date)),
+        _ => false,
+    };
+    day_match && time_match
+}
+
+/// `*`, day names (`mondays`, `saturday` or `sat`, separated by commas) or day letters (`smtwhfa`)
+fn weekday_matches(spec: &str, weekday: Weekday) -> bool {
+    if spec == "*" {
+        return true;
+    }
+    let (letter, day_name) = match weekday {
+        Weekday::Sun => ('s', "sunday"),
+        Weekday::Mon => ('m', "monday"),
+        Weekday::Tue => ('t', "tuesday"),
+        Weekday::Wed => ('w', "wednesday"),
+        Weekday::Thu => ('h', "thursday"),
+        Weekday::Fri => ('f', "friday"),
+        Weekday::Sat => ('a', "saturday"),
+    };
+    let spec = spec.to_lowercase();
+    // Names are checked first, "sat" is not the letters s, a and t
+    let names: Vec<&str> = spec.split(',').map(|v| v.trim()).filter(|v| !v.is_empty()).collect();
+    if !names.is_empty() && names.iter().all(|v| is_day_name(v)) {
+        return names.iter().any(|name| name.get(..3) == day_name.get(..3));
+    }
+    spec.len() <= 7 && spec.chars().all(|c| "smtwhfa".contains(c)) && spec.contains(letter)
+}
+
+/// Full day name, plural or abbreviated to three letters at least: `mondays`, `monday`, `mon`
+fn is_day_name(name: &str) -> bool {
+    const DAYS: [&str; 7] = ["sunday", "monday", "tuesday", "wednesday", "thursday", "friday", "saturday"];
+    name.len() >= 3 && DAYS.iter().any(|day| day.starts_with(name) || name.strip_suffix('s') == Some(day))
+}
+
+/// `2021-12-25`, `*-12-25` or a range `2021-12-24-2021-12-26`. Dots are accepted as separators.
+fn date_matches(spec: &str, date: &NaiveDateTime) -> bool {
+    let spec = spec.replace('.', "-");
+    let parts: Vec<&str> = spec.split('-').collect();
+    let current = (date.year() as i64, date.month() as i64, date.day() as i64);
+    match parts.len() {
+        3 => date_part(parts[0], current.0) && date_part(parts[1], current.1) && date_part(parts[2], current.2),
+        6 => {
+            let start: Vec<i64> = parts[..3].iter().filter_map(|v| v.parse().ok()).collect();
+            let end: Vec<i64> = parts[3..].iter().filter_map(|v| v.parse().ok()).collect();
+            if start.len() != 3 || end.len() != 3 {
+                return false;
+            }
+            (start[0], start[1], start[2]) <= current && current <= (end[0], end[1], end[2])
+        }
+        _ => false,
+    }
+}
+
+fn date_part(spec: &str, value: i64) -> bool {
+    spec == "*" || spec.parse::<i64>().map(|v| v == value).unwrap_or(false)
+}
+
+/// Applies a `s@from@to@flags` substitution. `i` compares ignoring the ASCII case. Backslash escapes of the
+/// replacement are removed (`\&` is written as `&`). Returns the URL if it changed.
+fn substitute(substitution: &str, url: &str) -> Option<String> {
+    let parts: Vec<&str> = substitution.get(1..)?.split('@').collect();
+    if parts.len() < 3 || !parts[0].is_empty() {
+        return None;
+    }
+    let (from, to, flags) = (parts[1], parts[2], parts.get(3).copied().unwrap_or(""));
+    if from.is_empty() {
+        return None;
+    }
+    let pos = if flags.contains('i') {
+        // Byte positions of the original URL, the ASCII case does not change the length
+        url.as_bytes().windows(from.len()).position(|v| v.eq_ignore_ascii_case(from.as_bytes()))
+    } else {
+        url.find(from)
+    }?;
+    let mut replacement = String::with_capacity(to.len());
+    let mut chars = to.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => replacement.extend(chars.next()),
+            _ => replacement.push(c),
+        }
+    }
+    Some(format!("{}{}{}", url.get(..pos)?, replacement, url.get(pos + from.len()..)?))
+}
+
+/// Replaces the placeholders of a squidGuard redirect URL
+fn fill_redirect(redirect: &str, client_ip: &str, user: &str, source: &str, destination: &str, url: &str) -> String {
+    let user = if user.is_empty() { "unknown" } else { user };
+    redirect
+        .replace("%a", client_ip)
+        .replace("%n", "unknown")
+        .replace("%i", user)
+        .replace("%s", source)
+        .replace("%t", destination)
+        .replace("%u", url)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{substitute, weekday_matches, PolicySimulator};
+    use chrono::Weekday;
+    use crate::fields;
+    use crate::squidguard::config::SquidGuardConfig;
+    use crate::squidguard::SquidGuardAction;
+    use usiem::events::field::SiemField;
+    use usiem::events::field_dictionary;
+    use usiem::events::SiemLog;
+    use usiem::events::field::SiemIp;
+
+    // 2021-02-15 10:00:00 UTC, a Monday
+    const MONDAY_MORNING: i64 = 1613383200000;
+    // 2021-02-13 22:00:00 UTC, a Saturday
+    const SATURDAY_NIGHT: i64 = 1613253600000;
+
+    fn docker_simulator() -> PolicySimulator {
+        let config = include_str!("../../docker/squidGuard.conf").parse::<SquidGuardConfig>().expect("Must work");
+        let mut simulator = PolicySimulator::new(config);
+        simulator.add_domains("porn", "pornpage.com");
+        simulator.add_domains("hacking", "hackpage.com");
+        simulator.add_urls("anonvpn", "example.com/vpn");
+        simulator
+    }
+
+    #[test]
+    fn test_docker_policy() {
+        let simulator = docker_simulator();
+        let verdict = simulator.verdict("172.17.0.1", "-", "pornpage.com:443", MONDAY_MORNING).expect("Must work");
+        assert_eq!(verdict.source, "default");
+        assert_eq!(verdict.acl, "default");
+        assert_eq!(verdict.destination, Some("porn".to_string()));
+        assert_eq!(verdict.action, SquidGuardAction::Redirect);
+        assert_eq!(verdict.redirect, Some("http://127.0.0.1:80/denied.html".to_string()));
+        let verdict = simulator.verdict("172.17.0.1", "-", "http://www.example.com/vpn/download", MONDAY_MORNING).expect("Must work");
+        assert_eq!(verdict.destination, Some("anonvpn".to_string()));
+        let verdict = simulator.verdict("172.17.0.1", "-", "http://www.google.com/", MONDAY_MORNING).expect("Must work");
+        assert_eq!(verdict.destination, Some("all".to_string()));
+        assert_eq!(verdict.action, SquidGuardAction::Pass);
+        assert_eq!(verdict.redirect, None);
+    }
+
+    #[test]
+    fn test_simulated_log() {
+        let simulator = docker_simulator();
+        let simulated = simulator.simulate("172.17.0.1", "-", "pornpage.com:443", 1613260953000).expect("Must work");
+        let log = "2021-02-14 00:02:33 [26] Request(default/porn/-) pornpage.com:443 172.17.0.1/172.17.0.1 - CONNECT REDIRECT";
+        let parsed = crate::squidguard::parse_log(SiemLog::new(log.to_string(), 0, SiemIp::V4(0))).expect("Must work");
+        for field in &[field_dictionary::SOURCE_IP, field_dictionary::URL_DOMAIN, field_dictionary::DESTINATION_PORT, field_dictionary::HTTP_RESPONSE_STATUS_CODE, field_dictionary::EVENT_OUTCOME, field_dictionary::RULE_NAME] {
+            assert_eq!(simulated.field(field), parsed.field(field));
+        }
+        assert_eq!(simulated.event_created(), parsed.event_created());
+        assert_eq!(simulated.field(fields::SQUIDGUARD_DESTINATION_GROUP), Some(&SiemField::from_str("porn")));
+        assert_eq!(simulated.field(field_dictionary::RULE_CATEGORY), Some(&SiemField::from_str("Pornography")));
+        let simulated = simulator.simulate("172.17.0.1", "CORP\\john smith", "http://www.google.com/search?q=test", 1613260953000).expect("Must work");
+        assert_eq!(simulated.field(field_dictionary::EVENT_OUTCOME), Some(&SiemField::from_str("ALLOW")));
+        assert_eq!(simulated.field(field_dictionary::USER_NAME), Some(&SiemField::User("john smith".to_string())));
+        assert_eq!(simulated.field(field_dictionary::USER_DOMAIN), Some(&SiemField::from_str("CORP")));
+    }
+
+    #[test]
+    fn test_sources_and_times() {
+        let content = r#"
+time workhours {
+    weekly mtwhf 08:00-17:00
+}
+src staff {
+    ip 10.0.0.0/255.255.0.0
+    user alice
+}
+src students {
+    ip 10.1.0.0/16 10.2.0.1-10.2.0.20
+}
+dest games {
+    domainlist games/domains
+    redirect http://proxy/games.html?client=%a&user=%i&url=%u
+}
+dest social {
+    domainlist social/domains
+}
+rewrite safesearch {
+    s@google.com/search?@google.com/search?safe=active\&@i
+}
+acl {
+    staff within workhours {
+        pass !games all
+    } else {
+        pass !social all
+        redirect http://proxy/after-hours.html
+    }
+    students {
+        pass !games all
+        rewrite safesearch
+    }
+    default {
+        pass none
+        redirect http://proxy/blocked.html?source=%s
+    }
+}
+"#;
+        let config = content.parse::<SquidGuardConfig>().expect("Must work");
+        let mut simulator = PolicySimulator::new(config);
+        simulator.add_domains("games", "chess.com");
+        simulator.add_domains("social", "facebook.com");
+        simulator.add_users("staff", "bob:Bob Smith\n");
+
+        let verdict = simulator.verdict("10.0.3.4", "-", "https://www.chess.com/play", MONDAY_MORNING).expect("Must work");
+        assert_eq!((verdict.source.as_str(), verdict.acl_else), ("staff", false));
+        assert_eq!(verdict.redirect, Some("http://proxy/games.html?client=10.0.3.4&user=-&url=https://www.chess.com/play".to_string()));
+        let verdict = simulator.verdict("10.0.3.4", "-", "https://www.chess.com/play", SATURDAY_NIGHT).expect("Must work");
+        assert_eq!((verdict.acl_else, verdict.action), (true, SquidGuardAction::Pass));
+        // The else branch has its own redirect
+        let verdict = simulator.verdict("10.0.3.4", "-", "https://www.facebook.com/", SATURDAY_NIGHT).expect("Must work");
+        assert_eq!((verdict.acl_else, verdict.action), (true, SquidGuardAction::Redirect));
+        assert_eq!(verdict.redirect, Some("http://proxy/after-hours.html".to_string()));
+        let verdict = simulator.verdict("192.168.1.1", "bob", "https://www.chess.com/play", MONDAY_MORNING).expect("Must work");
+        assert_eq!(verdict.source, "staff");
+
+        let verdict = simulator.verdict("10.2.0.15", "-", "http://www.google.com/search?q=test", MONDAY_MORNING).expect("Must work");
+        assert_eq!(verdict.source, "students");
+        assert_eq!(verdict.rewrite, Some("safesearch".to_string()));
+        assert_eq!(verdict.action, SquidGuardAction::Rewrite(Some("http://www.google.com/search?safe=active&q=test".to_string())));
+
+        let verdict = simulator.verdict("10.2.0.21", "-", "http://www.google.com/", MONDAY_MORNING).expect("Must work");
+        assert_eq!((verdict.source.as_str(), verdict.destination.as_deref()), ("default", Some("none")));
+        assert_eq!(verdict.redirect, Some("http://proxy/blocked.html?source=default".to_string()));
+    }
+
+    #[test]
+    fn test_substitute() {
+        assert_eq!(substitute("s@google.com/search?@google.com/search?safe=active\\&@i", "http://WWW.Google.COM/search?q=test"), Some("http://WWW.google.com/search?safe=active&q=test".to_string()));
+        // The lowercase of İ is longer, the offsets must be the ones of the original URL
+        assert_eq!(substitute("s@/SEARCH@/find@i", "http://example.com/İ/Search?q"), Some("http://example.com/İ/find?q".to_string()));
+        assert_eq!(substitute("s@/SEARCH@/find@", "http://example.com/search"), None);
+        assert_eq!(substitute("s@é@e@i", "http://example.com/É"), None);
+    }
+
+    #[test]
+    fn test_weekday_matches() {
+        assert!(weekday_matches("sat", Weekday::Sat));
+        assert!(!weekday_matches("sat", Weekday::Sun));
+        assert!(!weekday_matches("sat", Weekday::Tue));
+        assert!(weekday_matches("saturdays", Weekday::Sat));
+        assert!(weekday_matches("Monday,tuesdays", Weekday::Tue));
+        assert!(weekday_matches("smtwhfa", Weekday::Thu));
+        assert!(weekday_matches("sa", Weekday::Sat));
+        assert!(!weekday_matches("mtwhf", Weekday::Sat));
+        assert!(weekday_matches("*", Weekday::Sun));
+        assert!(!weekday_matches("xyz", Weekday::Sun));
+    }
+}
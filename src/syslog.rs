@@ -0,0 +1,98 @@
+use super::timezone::LogTimezone;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+/// Splits a syslog line sent by the program `tag` (`squid[26]: message` or `(squid-1)[26]: message`).
+/// Returns the pid of the tag, if any, and the message.
+pub fn split_syslog<'a>(log_line: &'a str, tag: &str) -> Option<(Option<u64>, &'a str)> {
+    if !log_line.starts_with("<") {
+        return None;
+    }
+    // The tag must not be part of a longer name: squid in squidGuard or in the hostname squidproxy
+    let tag_pos = log_line.match_indices(tag).map(|(pos, _)| pos).find(|pos| {
+        match log_line[pos + tag.len()..].chars().next() {
+            Some(c) => !c.is_alphanumeric(),
+            None => false,
+        }
+    })?;
+    let tag_end = tag_pos + log_line[tag_pos..].find(": ")?;
+    let tag_text = &log_line[tag_pos..tag_end];
+    let pid = match (tag_text.rfind("["), tag_text.ends_with("]")) {
+        (Some(pos), true) => tag_text[pos + 1..tag_text.len() - 1].parse::<u64>().ok(),
+        _ => None,
+    };
+    Some((pid, &log_line[tag_end + 2..]))
+}
+
+/// Timestamp of the syslog header. RFC 5424 headers (`<13>1 2021-02-14T00:02:33+01:00 host tag: ...`) have the offset.
+/// RFC 3164 headers (`<13>Feb 14 00:02:33 host tag: ...`) have no year nor timezone, so the year of the reference date
+/// is used, moved to the previous one if the date would be in the future. `Feb 29` is taken from the last leap year.
+pub fn syslog_timestamp(log_line: &str, timezone: &LogTimezone, reference_millis: i64) -> Option<i64> {
+    let header = if log_line.starts_with("<") {
+        &log_line[log_line.find(">")? + 1..]
+    } else {
+        log_line
+    };
+    // Month, day and hour. The day is padded with a space: "Feb  4"
+    let columns: Vec<&str> = header.split(' ').filter(|v| !v.is_empty()).take(3).collect();
+    if columns.len() < 3 {
+        return None;
+    }
+    if columns[0].chars().all(|c| c.is_ascii_digit()) {
+        return match DateTime::parse_from_rfc3339(columns[1]) {
+            Ok(date) => Some(date.timestamp_millis()),
+            Err(_) => None,
+        };
+    }
+    // 2000 is a leap year, so Feb 29 can be parsed
+    let text = format!("2000 {} {} {}", columns[0], columns[1], columns[2]);
+    let date = NaiveDateTime::parse_from_str(&text, "%Y %b %d %H:%M:%S").ok()?;
+    let reference = Utc.timestamp_millis_opt(reference_millis).single()?;
+    let mut year = reference.year();
+    // One day of margin for clock differences
+    let mut max_millis = reference_millis + 86_400_000;
+    // A leap year is found in 8 years at most (1900, 2100...)
+    for _ in 0..8 {
+        if let Some(day) = NaiveDate::from_ymd_opt(year, date.month(), date.day()) {
+            let millis = timezone.timestamp_millis(&day.and_time(date.time()))?;
+            if millis <= max_millis {
+                return Some(millis);
+            }
+            max_millis = i64::MAX;
+        }
+        year -= 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::{split_syslog, syslog_timestamp};
+    use crate::timezone::LogTimezone;
+
+    #[test]
+    fn test_split_syslog() {
+        assert_eq!(split_syslog("<13>Feb 14 00:02:33 proxy squidGuard[26]: Request(...)", "squidGuard"), Some((Some(26), "Request(...)")));
+        assert_eq!(split_syslog("<1>1 2020-09-25T16:23:25+02:00 OPNsense.localdomain (squid-1)[91300]: 1601051005.952 ...", "squid"), Some((Some(91300), "1601051005.952 ...")));
+        assert_eq!(split_syslog("<13>Feb 14 00:02:33 proxy squid: kid1| ERROR: x", "squid"), Some((None, "kid1| ERROR: x")));
+        assert_eq!(split_syslog("<13>Feb 14 00:02:33 squidproxy squid[26]: kid1| ERROR: x", "squid"), Some((Some(26), "kid1| ERROR: x")));
+        assert_eq!(split_syslog("<13>Feb 14 00:02:33 proxy squidGuard[26]: Request(...)", "squid"), None);
+        assert_eq!(split_syslog("<13>Feb 14 00:02:33 host sshd[22]: Accepted password for root", "squid"), None);
+        assert_eq!(split_syslog("1613260836.628 287 172.17.0.1 TCP_TUNNEL/200", "squid"), None);
+    }
+
+    #[test]
+    fn test_syslog_timestamp() {
+        let tz = "+01:00".parse::<LogTimezone>().expect("Must work");
+        // Reference: 2021-02-14 00:00:00 UTC
+        let reference = 1613260800000;
+        assert_eq!(syslog_timestamp("<13>Feb 14 01:02:33 proxy squidGuard: 2021-02-14 01:02:33 [26] Request(...)", &tz, reference), Some(1613260953000));
+        assert_eq!(syslog_timestamp("<13>Dec 31 23:00:00 proxy squidGuard: ...", &tz, reference), Some(1609452000000));
+        assert_eq!(syslog_timestamp("<13>Feb  4 01:00:00 proxy squidGuard: ...", &tz, reference), Some(1612396800000));
+        assert_eq!(syslog_timestamp("<13>1 2021-02-14T01:02:33+01:00 proxy squidGuard 26 - - ...", &tz, reference), Some(1613260953000));
+        assert_eq!(syslog_timestamp("2021-02-14 00:02:33 [26] Request(...)", &tz, reference), None);
+        // 2021 is not a leap year, the last Feb 29 was in 2020
+        assert_eq!(syslog_timestamp("<13>Feb 29 01:00:00 proxy squidGuard: ...", &tz, reference), Some(1582934400000));
+        // Reference: 2024-03-01 00:00:00 UTC
+        assert_eq!(syslog_timestamp("<13>Feb 29 01:00:00 proxy squidGuard: ...", &tz, 1709251200000), Some(1709164800000));
+    }
+}
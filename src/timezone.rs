@@ -0,0 +1,152 @@
+use chrono::{Duration, FixedOffset, LocalResult, NaiveDateTime, TimeZone, Utc};
+
+/// Timezone of the timestamps written without offset, like the squidGuard dates or RFC 3164 syslog headers.
+#[derive(Clone, Debug)]
+pub enum LogTimezone {
+    Utc,
+    /// Fixed offset from UTC, without daylight saving time
+    Fixed(FixedOffset),
+    /// IANA timezone from the embedded tz database: `Europe/Madrid`
+    #[cfg(feature = "tz")]
+    Named(chrono_tz::Tz),
+}
+
+impl Default for LogTimezone {
+    fn default() -> LogTimezone {
+        LogTimezone::Utc
+    }
+}
+
+impl std::str::FromStr for LogTimezone {
+    type Err = &'static str;
+
+    /// Accepts `UTC`, fixed offsets like `+02:00`, `-0530` or `+2`, and IANA names when the `tz` feature is enabled.
+    fn from_str(val: &str) -> Result<LogTimezone, &'static str> {
+        let val = val.trim();
+        if val.eq_ignore_ascii_case("UTC") || val.eq_ignore_ascii_case("GMT") || val == "Z" {
+            return Ok(LogTimezone::Utc);
+        }
+        if val.starts_with("+") || val.starts_with("-") {
+            return match parse_offset(val) {
+                Some(offset) => Ok(LogTimezone::Fixed(offset)),
+                None => Err("Invalid timezone offset"),
+            };
+        }
+        LogTimezone::from_name(val)
+    }
+}
+
+impl LogTimezone {
+    #[cfg(feature = "tz")]
+    fn from_name(val: &str) -> Result<LogTimezone, &'static str> {
+        match val.parse::<chrono_tz::Tz>() {
+            Ok(tz) => Ok(LogTimezone::Named(tz)),
+            Err(_) => Err("Unknown timezone"),
+        }
+    }
+
+    #[cfg(not(feature = "tz"))]
+    fn from_name(_val: &str) -> Result<LogTimezone, &'static str> {
+        Err("Timezone names require the tz feature")
+    }
+
+    /// Milliseconds since epoch of a local date.
+    /// During the DST fall back the repeated hour is taken as the first one (before the change).
+    /// Dates inside the DST spring forward gap do not exist and are read with the offset before the change.
+    pub fn timestamp_millis(&self, date: &NaiveDateTime) -> Option<i64> {
+        match self {
+            LogTimezone::Utc => Some(Utc.from_utc_datetime(date).timestamp_millis()),
+            LogTimezone::Fixed(offset) => local_millis(offset, date),
+            #[cfg(feature = "tz")]
+            LogTimezone::Named(tz) => local_millis(tz, date),
+        }
+    }
+
+    /// Local date of a timestamp in milliseconds since epoch
+    pub fn local_datetime(&self, millis: i64) -> Option<NaiveDateTime> {
+        let date = Utc.timestamp_millis_opt(millis).single()?;
+        match self {
+            LogTimezone::Utc => Some(date.naive_utc()),
+            LogTimezone::Fixed(offset) => Some(date.with_timezone(offset).naive_local()),
+            #[cfg(feature = "tz")]
+            LogTimezone::Named(tz) => Some(date.with_timezone(tz).naive_local()),
+        }
+    }
+
+    /// Parses a local date with a chrono format string
+    pub fn parse_millis(&self, text: &str, format: &str) -> Option<i64> {
+        match NaiveDateTime::parse_from_str(text, format) {
+            Ok(date) => self.timestamp_millis(&date),
+            Err(_) => None,
+        }
+    }
+}
+
+fn local_millis<T: TimeZone>(tz: &T, date: &NaiveDateTime) -> Option<i64> {
+    match tz.from_local_datetime(date) {
+        LocalResult::Single(v) => Some(v.timestamp_millis()),
+        LocalResult::Ambiguous(first, _) => Some(first.timestamp_millis()),
+        LocalResult::None => match tz.from_local_datetime(&(*date + Duration::hours(1))) {
+            LocalResult::Single(v) => Some(v.timestamp_millis() - 3_600_000),
+            LocalResult::Ambiguous(first, _) => Some(first.timestamp_millis() - 3_600_000),
+            LocalResult::None => None,
+        },
+    }
+}
+
+/// Parses `+02:00`, `-0530` or `+2`
+fn parse_offset(val: &str) -> Option<FixedOffset> {
+    let sign = if val.starts_with("-") { -1 } else { 1 };
+    let digits: String = val[1..].chars().filter(|c| *c != ':').collect();
+    if digits.is_empty() || digits.len() > 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = if digits.len() <= 2 {
+        (digits.parse::<i32>().ok()?, 0)
+    } else {
+        let split = digits.len() - 2;
+        (digits[..split].parse::<i32>().ok()?, digits[split..].parse::<i32>().ok()?)
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+#[cfg(test)]
+mod test {
+    use super::LogTimezone;
+    use chrono::NaiveDateTime;
+
+    #[test]
+    fn test_fixed_offsets() {
+        let date = NaiveDateTime::parse_from_str("2021-02-14 00:02:33", "%Y-%m-%d %H:%M:%S").expect("Must work");
+        assert_eq!("UTC".parse::<LogTimezone>().expect("Must work").timestamp_millis(&date), Some(1613260953000));
+        assert_eq!("+02:00".parse::<LogTimezone>().expect("Must work").timestamp_millis(&date), Some(1613260953000 - 7_200_000));
+        assert_eq!("-0530".parse::<LogTimezone>().expect("Must work").timestamp_millis(&date), Some(1613260953000 + 19_800_000));
+        assert_eq!("+1".parse::<LogTimezone>().expect("Must work").timestamp_millis(&date), Some(1613260953000 - 3_600_000));
+        assert!("+25:00".parse::<LogTimezone>().is_err());
+        assert!("+ab".parse::<LogTimezone>().is_err());
+        let local = "+02:00".parse::<LogTimezone>().expect("Must work").local_datetime(1613260953000 - 7_200_000);
+        assert_eq!(local, Some(date));
+    }
+
+    #[cfg(feature = "tz")]
+    #[test]
+    fn test_named_timezones() {
+        let tz = "Europe/Madrid".parse::<LogTimezone>().expect("Must work");
+        // Winter: UTC+1
+        let date = NaiveDateTime::parse_from_str("2021-02-14 01:02:33", "%Y-%m-%d %H:%M:%S").expect("Must work");
+        assert_eq!(tz.timestamp_millis(&date), Some(1613260953000));
+        // Summer: UTC+2
+        let date = NaiveDateTime::parse_from_str("2021-07-14 02:00:00", "%Y-%m-%d %H:%M:%S").expect("Must work");
+        assert_eq!(tz.timestamp_millis(&date), Some(1626220800000));
+        // Fall back: 02:30 happens twice, the first one is UTC+2
+        let date = NaiveDateTime::parse_from_str("2021-10-31 02:30:00", "%Y-%m-%d %H:%M:%S").expect("Must work");
+        assert_eq!(tz.timestamp_millis(&date), Some(1635640200000));
+        // Spring forward: 02:30 does not exist, it is read as UTC+1
+        let date = NaiveDateTime::parse_from_str("2021-03-28 02:30:00", "%Y-%m-%d %H:%M:%S").expect("Must work");
+        assert_eq!(tz.timestamp_millis(&date), Some(1616891400000));
+        assert!("Mars/Olympus_Mons".parse::<LogTimezone>().is_err());
+    }
+}